@@ -2,16 +2,97 @@
  * structs to connect components together
 */
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub const NB_CHANNELS: usize = 16;
-pub const DATA_SIZE: usize = std::mem::size_of::<Data>();
 
-// Data that is transmitted over wires
+// define index for components and wires
+pub type Index = u32;
+
+// Data that is transmitted over wires, wide enough for every data width
 pub type Channel = u8;
-pub type Data = u16;
+pub type Data = u64;
+
+// number of bits carried by the wires of a circuit, in the lowest bits of the data
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Resource, Deserialize, Serialize)]
+pub struct DataWidth(pub u32);
+
+impl Default for DataWidth {
+    fn default() -> Self {
+        Self(16)
+    }
+}
+
+impl DataWidth {
+    // from a single bit up to the size of the data
+    pub fn is_valid(&self) -> bool {
+        0 < self.0 && self.0 <= Data::BITS
+    }
+
+    // all-ones at this width
+    pub fn mask(&self) -> Data {
+        Data::MAX
+            .checked_shr(Data::BITS.saturating_sub(self.0))
+            .unwrap_or(0)
+    }
+
+    // largest value of this width closest to the data
+    pub fn clamp(&self, data: Data) -> Data {
+        data.min(self.mask())
+    }
+
+    // number of bytes of a word in binary files
+    pub fn bytes(&self) -> usize {
+        (self.0 as usize).div_ceil(8)
+    }
+}
+
+// how the data driven by several components on the same wire is combined
+#[derive(Clone, Copy, Component, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Resolution {
+    // bitwise or of the drivers
+    #[default]
+    WiredOr,
+    // bitwise and of the drivers
+    WiredAnd,
+    // the drivers must agree, their wired-or is kept when they do not
+    Strict,
+}
+
+impl Resolution {
+    // combine the data of every driver, a wire without driver is null
+    pub fn resolve(&self, values: &[Data]) -> Data {
+        match self {
+            Resolution::WiredAnd if !values.is_empty() => {
+                values.iter().fold(Data::MAX, |a, b| a & b)
+            }
+            Resolution::WiredAnd => 0,
+            _ => values.iter().fold(0, |a, b| a | b),
+        }
+    }
+
+    // whether the drivers disagree on a strict wire
+    pub fn conflicts(&self, values: &[Data]) -> bool {
+        *self == Resolution::Strict && values.windows(2).any(|w| w[0] != w[1])
+    }
+}
+
+// drivers of a strict wire disagreeing, sent when the conflict appears or its data changes,
+// with the index of the wire in the schematic and the data of each driver
+#[derive(Clone, Debug, Event, PartialEq, Eq)]
+pub struct DriverConflict {
+    pub wire: Index,
+    pub tick: u64,
+    pub values: Vec<Data>,
+}
+
+// strict wires which drivers currently disagree, with the data of each driver
+#[derive(Default, Resource)]
+pub struct WireConflicts(pub HashMap<Entity, Vec<Data>>);
 
 // data on the wire on the previous tick
-#[derive(Component)]
+#[derive(Component, PartialEq)]
 pub struct DataPrev(pub Data);
 
 // data on the wire on the next tick
@@ -29,3 +110,72 @@ pub struct PinsIn(pub Vec<Entity>);
 // outputs leaving a component
 #[derive(Component)]
 pub struct PinsOut(pub Vec<Entity>);
+
+// data driven by a component on each of its output pins
+#[derive(Component, PartialEq)]
+pub struct DataOut(pub Vec<Data>);
+
+// components reading from a wire
+#[derive(Component)]
+pub struct Fanout(pub Vec<Entity>);
+
+// components writing to a wire with the index of their output pin
+#[derive(Component)]
+pub struct Drivers(pub Vec<(Entity, usize)>);
+
+// values of input wires combined by channel, none when no wire uses the channel
+pub type ByChannel = [Option<Data>; NB_CHANNELS];
+
+// components to evaluate on the current tick
+#[derive(Default, Resource)]
+pub struct DirtyComps(pub Vec<Entity>);
+
+// every component is evaluated and every wire resolved on the next tick,
+// requested when the state of the whole circuit is replaced
+#[derive(Default, Resource)]
+pub struct DirtyAll(pub bool);
+
+impl DataOut {
+    // nothing is driven before the first evaluation
+    pub fn new(nb_pins: usize) -> Self {
+        Self(vec![0; nb_pins])
+    }
+}
+
+// wired-or the value of an input wire with the other wires of the same channel
+#[inline]
+pub fn combine_channel(inputs: &mut ByChannel, channel: Channel, data: Data) {
+    let slot = &mut inputs[channel as usize];
+    *slot = Some(slot.unwrap_or(0) | data);
+}
+
+// read the input wires of a component by channel
+pub fn read_by_channel(
+    pins_in: &PinsIn,
+    prev_query: &Query<(&PinChannel, &DataPrev)>,
+) -> ByChannel {
+    let mut inputs: ByChannel = [None; NB_CHANNELS];
+    for id in pins_in.0.iter() {
+        if let Ok((index, pin)) = prev_query.get(*id) {
+            combine_channel(&mut inputs, index.0, pin.0);
+        }
+    }
+    inputs
+}
+
+// boolean state of a component as data
+#[inline]
+pub fn data_from_bool(state: bool) -> Data {
+    if state {
+        Data::MAX
+    } else {
+        0
+    }
+}
+
+// drive the same value on every output pin, only flag a change if necessary
+pub fn drive_all(data_out: &mut Mut<DataOut>, data: Data) {
+    if data_out.0.iter().any(|v| *v != data) {
+        data_out.0.fill(data);
+    }
+}
@@ -0,0 +1,98 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/* Clock Generator Entity: CompClock, PinsOut, DataOut */
+// number of ticks of each half of the period, the phase shifts the start of the period,
+// clocks sharing a period but not a phase form a multi-phase clock
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClockParams {
+    pub high: u32,
+    pub low: u32,
+    pub phase: u32,
+}
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompClock {
+    pub params: ClockParams,
+    // position in the period of the next tick
+    pub position: u64,
+}
+
+impl CompClock {
+    pub fn new(params: ClockParams) -> Self {
+        let period = params.high as u64 + params.low as u64;
+        Self {
+            params,
+            position: (params.phase as u64).checked_rem(period).unwrap_or(0),
+        }
+    }
+
+    // move to the next tick, return the data to output,
+    // the clock starts high unless it has a phase
+    pub fn update(&mut self) -> Data {
+        let period = self.params.high as u64 + self.params.low as u64;
+        let high = self.position < self.params.high as u64;
+        self.position = (self.position + 1).checked_rem(period).unwrap_or(0);
+        data_from_bool(high)
+    }
+}
+
+// clocks have no input, they are evaluated on every tick
+pub fn sys_tick(mut comp_query: Query<(&mut CompClock, &mut DataOut)>) {
+    for (mut clock, mut data_out) in comp_query.iter_mut() {
+        let data = clock.update();
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompType, Index};
+
+    fn states(params: ClockParams, n: usize) -> Vec<bool> {
+        let mut clock = CompClock::new(params);
+        (0..n).map(|_| clock.update() != 0).collect()
+    }
+
+    #[test]
+    fn period_and_phase() {
+        let params = ClockParams {
+            high: 2,
+            low: 1,
+            phase: 0,
+        };
+        let t = true;
+        let f = false;
+        assert_eq!(states(params, 7), [t, t, f, t, t, f, t]);
+        let shifted = ClockParams { phase: 4, ..params };
+        assert_eq!(states(shifted, 4), [t, f, t, t]);
+        let stopped = ClockParams::default();
+        assert_eq!(states(stopped, 2), [f, f]);
+    }
+
+    #[test]
+    fn two_phases() {
+        let clock = |phase| {
+            let params = ClockParams {
+                high: 1,
+                low: 1,
+                phase,
+            };
+            comp(CompType::Clock(params), vec![], vec![phase as Index])
+        };
+        let schema = schema(vec![wire(0), wire(0)], vec![clock(0), clock(1)]);
+        on_every_backend(&schema, |sim| {
+            let wires: Vec<_> = (0..4)
+                .map(|_| {
+                    sim.step(1);
+                    read_wires(sim)
+                })
+                .collect();
+            let on = sim.width().mask();
+            assert_eq!(wires, [[on, 0], [0, on], [on, 0], [0, on]]);
+            wires
+        });
+    }
+}
@@ -0,0 +1,146 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+// components take at most this amount of ticks to drive their outputs
+pub const MAX_DELAY: u32 = 1 << 8;
+
+/* Pipeline of a delayed component: data evaluated but not driven yet */
+// every component drives its outputs on the tick it is evaluated, a component delayed by
+// more than one tick drives the data it evaluated `delay - 1` ticks before
+#[derive(Component, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Pipeline {
+    // data evaluated on the last ticks, one stage of a word per pin after the other,
+    // in a ring which oldest stage starts at `head`
+    stages: Vec<Data>,
+    head: usize,
+    // data driven on the output pins
+    driven: Vec<Data>,
+}
+
+impl Pipeline {
+    pub fn new(delay: u32, nb_pins: usize) -> Self {
+        let nb_stages = delay.saturating_sub(1) as usize;
+        Self {
+            stages: vec![0; nb_stages * nb_pins],
+            head: 0,
+            driven: vec![0; nb_pins],
+        }
+    }
+
+    // number of ticks the data waits before being driven
+    pub fn nb_stages(&self) -> usize {
+        self.stages
+            .len()
+            .checked_div(self.driven.len())
+            .unwrap_or(0)
+    }
+
+    // whether shifting the data evaluated would change nothing
+    pub fn is_steady(&self, data: &[Data]) -> bool {
+        let nb_pins = self.driven.len().max(1);
+        self.driven == data && self.stages.chunks_exact(nb_pins).all(|stage| stage == data)
+    }
+
+    // move to the next tick with the data evaluated on it,
+    // the oldest stage is driven and replaced by the data
+    pub fn shift(&mut self, data: &[Data]) {
+        let nb_stages = self.nb_stages();
+        if nb_stages == 0 {
+            self.driven.copy_from_slice(data);
+            return;
+        }
+        let nb_pins = self.driven.len();
+        let oldest = &mut self.stages[self.head * nb_pins..(self.head + 1) * nb_pins];
+        self.driven.copy_from_slice(oldest);
+        oldest.copy_from_slice(data);
+        self.head = (self.head + 1) % nb_stages;
+    }
+
+    // data driven on the output pins
+    pub fn driven(&self) -> &[Data] {
+        &self.driven
+    }
+}
+
+// delayed components keep moving their data on every tick, even without being evaluated
+pub fn sys_tick(mut comp_query: Query<(&DataOut, &mut Pipeline)>) {
+    for (data_out, mut pipeline) in comp_query.iter_mut() {
+        if !pipeline.is_steady(&data_out.0) {
+            pipeline.shift(&data_out.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompKind, CompType, Error, SchemaComp};
+
+    #[test]
+    fn shifts_data() {
+        let mut pipeline = Pipeline::new(3, 1);
+        pipeline.shift(&[1]);
+        pipeline.shift(&[2]);
+        assert_eq!(pipeline.driven(), [0]);
+        pipeline.shift(&[3]);
+        assert_eq!(pipeline.driven(), [1]);
+        assert!(!pipeline.is_steady(&[3]));
+        pipeline.shift(&[3]);
+        pipeline.shift(&[3]);
+        assert!(pipeline.is_steady(&[3]));
+    }
+
+    #[test]
+    fn delay_line() {
+        let delayed = |delay| SchemaComp {
+            delay: Some(delay),
+            ..gate(Operator::Or, vec![0], vec![1])
+        };
+        let schema = schema(
+            vec![wire(0), wire(0), wire(0)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                delayed(4),
+                gate(Operator::Or, vec![1], vec![2]),
+            ],
+        )
+        .with_delay(CompKind::Gate, 2);
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            sim.write_input(0, 9);
+            let wires: Vec<_> = (0..8)
+                .map(|_| {
+                    sim.step(1);
+                    sim.read_wire(2).unwrap()
+                })
+                .collect();
+            // one tick for the input, four for the delay line, two for the last gate
+            assert_eq!(wires, [0, 0, 0, 0, 0, 0, 9, 9]);
+
+            // the data on its way through the pipeline is part of the snapshots
+            sim.write_input(0, 5);
+            sim.step(3);
+            let snapshot = sim.snapshot();
+            sim.step(5);
+            assert_eq!(sim.read_wire(2), Some(5));
+            sim.restore(&snapshot).unwrap();
+            sim.step(2);
+            assert_eq!(sim.read_wire(2), Some(9));
+            sim.step(3);
+            assert_eq!(sim.read_wire(2), Some(5));
+            wires
+        });
+
+        // the delay of the type applies to every gate, not only to the operator given,
+        // unless the component has its own
+        assert_eq!(schema.delay(1), 4);
+        assert_eq!(schema.delay(2), 2);
+        assert_eq!(schema.delay(0), 1);
+        let schema = schema.with_delay(CompKind::Input, 0);
+        assert!(matches!(
+            schema.verify().unwrap_err()[..],
+            [Error::CompDelay(0, 0)]
+        ));
+    }
+}
@@ -1,15 +1,17 @@
 use super::*;
 
-/* Entity Demultiplexer: CompDemux, PinsIn, PinsOut */
+/* Entity Demultiplexer: CompDemux, PinsIn, PinsOut, DataOut */
 #[derive(Component)]
 pub struct CompDemux(pub Data);
 
 pub fn sys_tick(
-    comp_query: Query<(&CompDemux, &PinsIn, &PinsOut)>,
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&CompDemux, &PinsIn, &PinsOut, &mut DataOut)>,
     prev_query: Query<&DataPrev>,
-    mut next_query: Query<(&PinChannel, &mut DataNext)>,
+    chan_query: Query<&PinChannel>,
 ) {
-    for (output, pins_in, pins_out) in comp_query.iter() {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((output, pins_in, pins_out, mut data_out)) = iter.fetch_next() {
         // find the values of input wires
         let mut data: Data = 0;
         for id in pins_in.0.iter() {
@@ -19,12 +21,14 @@ pub fn sys_tick(
         }
 
         // apply the value to all output wires
-        for id in pins_out.0.iter() {
-            if let Ok((index, mut pin)) = next_query.get_mut(*id) {
-                if ((data >> index.0) & 1) != 1 {
-                    pin.0 |= output.0;
-                }
-            }
-        }
+        let values = pins_out
+            .0
+            .iter()
+            .map(|id| match chan_query.get(*id) {
+                Ok(index) if ((data >> index.0) & 1) != 1 => output.0,
+                _ => 0,
+            })
+            .collect();
+        data_out.set_if_neq(DataOut(values));
     }
 }
@@ -0,0 +1,165 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/* Display Entity (LED, 7-segment digit, pixel panel): DisplayState, PinsIn */
+// what a display shows, independent of the rendering so that headless runs can check it
+#[derive(Component, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DisplayState {
+    // lit when any input wire is not null
+    Led(bool),
+    // hexadecimal digit of the lowest bits of the input wires combined,
+    // with the segments a to g on the bits 0 to 6
+    Digit { value: u8, segments: u8 },
+    // rows are driven by the input wires of their channel, pixels by the bits of the row
+    Panel { width: u8, rows: Vec<Data> },
+}
+
+// segments lit for each hexadecimal digit
+const SEGMENTS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+
+impl DisplayState {
+    pub fn led() -> Self {
+        Self::Led(false)
+    }
+
+    pub fn digit() -> Self {
+        Self::Digit {
+            value: 0,
+            segments: SEGMENTS[0],
+        }
+    }
+
+    // the size has been checked against the number of bits and of channels
+    pub fn panel(width: u8, height: u8) -> Self {
+        Self::Panel {
+            width,
+            rows: vec![0; height as usize],
+        }
+    }
+
+    // show the data of the input wires
+    pub fn update(&mut self, inputs: &ByChannel) {
+        let any = inputs
+            .iter()
+            .fold(0, |data, value| data | value.unwrap_or(0));
+        match self {
+            Self::Led(on) => *on = any != 0,
+            Self::Digit { value, segments } => {
+                *value = (any & 0xf) as u8;
+                *segments = SEGMENTS[*value as usize];
+            }
+            Self::Panel { width, rows } => {
+                let mask = Data::MAX
+                    .checked_shr(Data::BITS - *width as u32)
+                    .unwrap_or(0);
+                for (row, input) in rows.iter_mut().zip(inputs.iter()) {
+                    *row = input.unwrap_or(0) & mask;
+                }
+            }
+        }
+    }
+
+    // whether the pixel of a panel is lit, out of bound pixels are not
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        match self {
+            Self::Panel { width, rows } if x < *width => {
+                rows.get(y as usize).is_some_and(|row| (row >> x) & 1 == 1)
+            }
+            _ => false,
+        }
+    }
+}
+
+// what the display shows as text, a panel is drawn row by row with its lit pixels as #
+impl fmt::Display for DisplayState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Led(on) => write!(f, "led {}", if *on { "on" } else { "off" }),
+            Self::Digit { value, .. } => write!(f, "digit {:x}", value),
+            Self::Panel { width, rows } => {
+                write!(f, "panel")?;
+                for y in 0..rows.len() as u8 {
+                    let row: String = (0..*width)
+                        .map(|x| if self.pixel(x, y) { '#' } else { '.' })
+                        .collect();
+                    write!(f, "\n{}", row)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut DisplayState, &PinsIn)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut display, pins_in)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        display.update(&inputs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompType, Error};
+
+    #[test]
+    fn shows_inputs() {
+        let schema = schema(
+            vec![wire(0), wire(2)],
+            vec![
+                comp(CompType::Input, vec![], vec![0, 1]),
+                comp(CompType::Led, vec![0], vec![]),
+                comp(CompType::Digit, vec![0, 1], vec![]),
+                comp(CompType::Panel(4, 3), vec![0, 1], vec![]),
+            ],
+        );
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            assert_eq!(sim.display(1), Some(DisplayState::Led(false)));
+            assert_eq!(sim.display(2), Some(DisplayState::digit()));
+            sim.write_input(0, 0x31);
+            sim.write_input(2, 0x0a);
+            sim.step(2);
+            assert_eq!(sim.display(1), Some(DisplayState::Led(true)));
+            let digit = DisplayState::Digit {
+                value: 0xb,
+                segments: 0x7c,
+            };
+            assert_eq!(sim.display(2), Some(digit));
+            let panel = sim.display(3).unwrap();
+            assert!(panel.pixel(0, 0) && !panel.pixel(1, 0) && !panel.pixel(4, 0));
+            assert!(panel.pixel(1, 2) && panel.pixel(3, 2) && !panel.pixel(2, 2));
+            assert!(!panel.pixel(0, 1) && !panel.pixel(0, 3));
+            assert_eq!(panel.to_string(), "panel\n#...\n....\n.#.#");
+            assert_eq!(sim.display(0), None);
+            panel
+        });
+    }
+
+    #[test]
+    fn rejects_invalid_displays() {
+        let schema = schema(
+            vec![wire(0)],
+            vec![
+                comp(CompType::Panel(16, 16), vec![0], vec![]),
+                comp(CompType::Panel(17, 1), vec![0], vec![]),
+                comp(CompType::Panel(1, 17), vec![0], vec![]),
+                comp(CompType::Led, vec![], vec![0]),
+            ],
+        );
+        let errors = schema.verify().unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [Error::Display(1), Error::Display(2), Error::Display(3)]
+        ));
+    }
+}
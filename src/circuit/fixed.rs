@@ -1,16 +1,13 @@
 use super::*;
 
-/* Fixed Value Entity: CompFixed, PinsOut */
+/* Fixed Value Entity: CompFixed, PinsOut, DataOut */
 #[derive(Component)]
 pub struct CompFixed(pub Data);
 
-pub fn sys_tick(comp_query: Query<(&CompFixed, &PinsOut)>, mut next_query: Query<&mut DataNext>) {
-    for (constant, pins_out) in comp_query.iter() {
+// the value only needs to be driven once
+pub fn sys_tick(mut comp_query: Query<(&CompFixed, &mut DataOut), Changed<CompFixed>>) {
+    for (constant, mut data_out) in comp_query.iter_mut() {
         // apply the value to all output wires
-        for id in pins_out.0.iter() {
-            if let Ok(mut pin) = next_query.get_mut(*id) {
-                pin.0 |= constant.0;
-            }
-        }
+        drive_all(&mut data_out, constant.0);
     }
 }
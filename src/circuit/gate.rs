@@ -2,8 +2,10 @@ use super::*;
 use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
 
-/* Logic Gate Entity: Operator, PinsIn, PinsOut */
-#[derive(Clone, Copy, Component, Deserialize, Serialize)]
+/* Logic Gate Entity: Operator, OverflowMode, PinsIn, PinsOut, DataOut */
+// operands are ordered by the channel of their input wire,
+// operators which are not commutative fold them from left to right
+#[derive(Clone, Copy, Component, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Operator {
     Or,
     And,
@@ -13,15 +15,112 @@ pub enum Operator {
     Mul,
     Min,
     Max,
+    Xor,
+    Xnor,
+    // complement of the first operand
+    Not,
+    Sub,
+    // division by zero gives all-ones
+    Div,
+    // modulo by zero gives the dividend
+    Mod,
+    // shifting by the data size or more gives zero
+    Shl,
+    Shr,
+    // comparisons give all-ones when true for every pair of operands, zero otherwise
+    Eq,
+    Lt,
+    Gt,
+}
+
+// behavior of arithmetic operators (Add, Sub, Mul) when the result does not fit
+#[derive(Clone, Copy, Component, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OverflowMode {
+    // keep the lowest bits of the result
+    #[default]
+    Wrapping,
+    // clamp the result to zero or all-ones of the data width
+    Saturating,
+}
+
+impl OverflowMode {
+    // wrapped results are cut to the data width afterwards
+    #[inline]
+    fn add(self, a: Data, b: Data, mask: Data) -> Data {
+        match self {
+            OverflowMode::Wrapping => a.wrapping_add(b),
+            OverflowMode::Saturating => a.saturating_add(b).min(mask),
+        }
+    }
+
+    #[inline]
+    fn sub(self, a: Data, b: Data) -> Data {
+        match self {
+            OverflowMode::Wrapping => a.wrapping_sub(b),
+            OverflowMode::Saturating => a.saturating_sub(b),
+        }
+    }
+
+    #[inline]
+    fn mul(self, a: Data, b: Data, mask: Data) -> Data {
+        match self {
+            OverflowMode::Wrapping => a.wrapping_mul(b),
+            OverflowMode::Saturating => a.saturating_mul(b).min(mask),
+        }
+    }
+}
+
+impl Operator {
+    // compute the output value from the values of input wires at the data width,
+    // commutative operators start from their identity element
+    pub fn eval(&self, overflow: OverflowMode, width: DataWidth, values: &[Data]) -> Data {
+        let mask = width.mask();
+        let bits = width.0 as Data;
+        let iter = values.iter().copied();
+        let data = match self {
+            Operator::Or => iter.fold(0, |a, b| a | b),
+            Operator::And => iter.fold(Data::MAX, |a, b| a & b),
+            Operator::Nor => !iter.fold(0, |a, b| a | b),
+            Operator::Nand => !iter.fold(Data::MAX, |a, b| a & b),
+            Operator::Add => iter.fold(0, |a, b| overflow.add(a, b, mask)),
+            Operator::Mul => iter.fold(1, |a, b| overflow.mul(a, b, mask)),
+            Operator::Min => iter.fold(Data::MAX, min),
+            Operator::Max => iter.fold(0, max),
+            Operator::Xor => iter.fold(0, |a, b| a ^ b),
+            Operator::Xnor => !iter.fold(0, |a, b| a ^ b),
+            Operator::Not => !values.first().copied().unwrap_or(0),
+            Operator::Sub => fold(values, |a, b| overflow.sub(a, b)),
+            Operator::Div => fold(values, |a, b| a.checked_div(b).unwrap_or(Data::MAX)),
+            Operator::Mod => fold(values, |a, b| a.checked_rem(b).unwrap_or(a)),
+            // the amount is compared as data, it may not fit in 32 bits
+            Operator::Shl => fold(values, |a, b| if b < bits { a << b } else { 0 }),
+            Operator::Shr => fold(values, |a, b| if b < bits { a >> b } else { 0 }),
+            Operator::Eq => data_from_bool(values.windows(2).all(|w| w[0] == w[1])),
+            Operator::Lt => data_from_bool(values.windows(2).all(|w| w[0] < w[1])),
+            Operator::Gt => data_from_bool(values.windows(2).all(|w| w[0] > w[1])),
+        };
+        data & mask
+    }
+}
+
+// fold operands from left to right starting with the first one
+#[inline]
+fn fold(values: &[Data], func: impl Fn(Data, Data) -> Data) -> Data {
+    match values.split_first() {
+        Some((first, rest)) => rest.iter().fold(*first, |a, b| func(a, *b)),
+        None => 0,
+    }
 }
 
 // handle logic gates
 pub fn sys_tick(
-    comp_query: Query<(&Operator, &PinsIn, &PinsOut)>,
+    width: Res<DataWidth>,
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&Operator, &OverflowMode, &PinsIn, &mut DataOut)>,
     prev_query: Query<&DataPrev>,
-    mut next_query: Query<&mut DataNext>,
 ) {
-    for (operator, pins_in, pins_out) in comp_query.iter() {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((operator, overflow, pins_in, mut data_out)) = iter.fetch_next() {
         // find the values of input wires
         let mut values = Vec::<Data>::with_capacity(pins_in.0.len());
         for id in pins_in.0.iter() {
@@ -30,30 +129,64 @@ pub fn sys_tick(
             }
         }
 
-        // compute the output value
-        let mut data: Data = 0;
-        match operator {
-            Operator::Or => values.iter().for_each(|v| data |= *v),
-            Operator::And => values.iter().for_each(|v| data &= *v),
-            Operator::Nor => {
-                values.iter().for_each(|v| data |= *v);
-                data = !data;
-            }
-            Operator::Nand => {
-                values.iter().for_each(|v| data &= *v);
-                data = !data;
-            }
-            Operator::Add => values.iter().for_each(|v| data += *v),
-            Operator::Mul => values.iter().for_each(|v| data *= *v),
-            Operator::Min => values.iter().for_each(|v| data = min(data, *v)),
-            Operator::Max => values.iter().for_each(|v| data = max(data, *v)),
-        }
-
         // apply the value to all output wires
-        for id in pins_out.0.iter() {
-            if let Ok(mut pin) = next_query.get_mut(*id) {
-                pin.0 |= data;
-            }
-        }
+        drive_all(&mut data_out, operator.eval(*overflow, *width, &values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+    use crate::simulator::Simulator;
+
+    fn eval(operator: Operator, values: &[Data]) -> Data {
+        operator.eval(OverflowMode::Wrapping, DataWidth::default(), values)
+    }
+
+    #[test]
+    fn extended_operators() {
+        assert_eq!(eval(Operator::Sub, &[10, 3, 2]), 5);
+        assert_eq!(eval(Operator::Div, &[10, 0]), 0xffff);
+        assert_eq!(eval(Operator::Mod, &[10, 0]), 10);
+        assert_eq!(eval(Operator::Shl, &[1, 3]), 8);
+        assert_eq!(eval(Operator::Shl, &[1, 16]), 0);
+        assert_eq!(eval(Operator::Shr, &[8, 2]), 2);
+        assert_eq!(eval(Operator::Lt, &[1, 3]), 0xffff);
+        assert_eq!(eval(Operator::Gt, &[1, 3]), 0);
+        assert_eq!(eval(Operator::Not, &[5]), 0xffff ^ 5);
+        assert_eq!(eval(Operator::Xnor, &[5, 3]), 0xffff ^ 6);
+    }
+
+    #[test]
+    fn other_widths() {
+        let byte = DataWidth(8);
+        let wrap = OverflowMode::Wrapping;
+        let sat = OverflowMode::Saturating;
+        assert_eq!(Operator::Add.eval(wrap, byte, &[200, 100]), 44);
+        assert_eq!(Operator::Add.eval(sat, byte, &[200, 100]), 0xff);
+        assert_eq!(Operator::Mul.eval(sat, byte, &[16, 16]), 0xff);
+        assert_eq!(Operator::Not.eval(wrap, byte, &[5]), 0xff ^ 5);
+        assert_eq!(Operator::Shl.eval(wrap, byte, &[1, 7, 1]), 0);
+        let wide = DataWidth(64);
+        assert_eq!(Operator::Mul.eval(sat, wide, &[Data::MAX, 2]), Data::MAX);
+        assert_eq!(Operator::Nor.eval(wrap, wide, &[]), Data::MAX);
+        assert_eq!(Operator::Shl.eval(wrap, wide, &[1, 40]), 1 << 40);
+    }
+
+    #[test]
+    fn operands_follow_channels() {
+        let schema = schema(
+            vec![wire(3), wire(1), wire(0)],
+            vec![
+                comp(CompType::Fixed(2), vec![], vec![0]),
+                comp(CompType::Fixed(10), vec![], vec![1]),
+                gate(Operator::Sub, vec![0, 1], vec![2]),
+            ],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        sim.step(2);
+        assert_eq!(sim.read_wire(2), Some(8));
     }
 }
@@ -1,5 +1,8 @@
 use super::*;
 use bevy::input::{keyboard, ButtonState};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{error, fmt, fs, path};
 
 /* Keyboard Input Device */
 #[derive(Default, Resource)]
@@ -7,22 +10,147 @@ pub struct InputDevice {
     buffer: [Data; NB_CHANNELS],
 }
 
-/* Keyboard Input Entity: CompInput, PinsOut */
+impl InputDevice {
+    // read the value of a channel
+    pub fn get(&self, channel: Channel) -> Data {
+        self.buffer[channel as usize]
+    }
+
+    // overwrite the value of a channel
+    pub fn set(&mut self, channel: Channel, data: Data) {
+        self.buffer[channel as usize] = data;
+    }
+}
+
+// bit of a channel of the input device driven by a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBit {
+    pub channel: Channel,
+    pub bit: u8,
+}
+
+impl KeyBit {
+    // whether the bit exists in the buffer of the input device and reaches the wires
+    pub fn is_valid(&self, width: DataWidth) -> bool {
+        (self.channel as usize) < NB_CHANNELS && (self.bit as u32) < width.0
+    }
+}
+
+// a key mapped outside of the buffer of the input device or of the width of the wires
+#[derive(Debug)]
+pub struct KeyMapError(pub KeyCode, pub KeyBit);
+impl error::Error for KeyMapError {}
+impl fmt::Display for KeyMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let KeyMapError(key, to) = self;
+        write!(
+            f,
+            "Key Map Error for {:?}, channel={} bit={}",
+            key, to.channel, to.bit
+        )
+    }
+}
+
+/* Key Map: which bit of the input device each key drives, the same on every platform */
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
+pub struct KeyMap(HashMap<KeyCode, KeyBit>);
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::directions()
+    }
+}
+
+impl KeyMap {
+    // no key drives the input device
+    pub fn empty() -> Self {
+        Self(HashMap::new())
+    }
+
+    // W, A, S, D on the first bits of channel 0 and the arrows on the first bits of channel 1,
+    // both in the order up, left, down, right
+    pub fn directions() -> Self {
+        let mut map = Self::empty();
+        let wasd = [KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D];
+        let arrows = [KeyCode::Up, KeyCode::Left, KeyCode::Down, KeyCode::Right];
+        for (channel, keys) in [wasd, arrows].iter().enumerate() {
+            map.insert_row(channel as Channel, keys);
+        }
+        map
+    }
+
+    // letters A to P on channel 0, Q to Z on channel 1, digits 0 to 9 followed by space
+    // and return on channel 2, each row starting on the first bit
+    pub fn ascii() -> Self {
+        use KeyCode::*;
+        let mut map = Self::empty();
+        map.insert_row(0, &[A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P]);
+        map.insert_row(1, &[Q, R, S, T, U, V, W, X, Y, Z]);
+        map.insert_row(
+            2,
+            &[
+                Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Space, Return,
+            ],
+        );
+        map
+    }
+
+    // bit driven by the key if it is mapped
+    pub fn get(&self, key: KeyCode) -> Option<KeyBit> {
+        self.0.get(&key).copied()
+    }
+
+    // map the keys on consecutive bits of a channel
+    fn insert_row(&mut self, channel: Channel, keys: &[KeyCode]) {
+        for (bit, key) in keys.iter().enumerate() {
+            let to = KeyBit {
+                channel,
+                bit: bit as u8,
+            };
+            self.0.insert(*key, to);
+        }
+    }
+
+    // check that every key drives a bit carried by the wires of the given width
+    pub fn check(&self, width: DataWidth) -> Result<(), KeyMapError> {
+        match self.0.iter().find(|(_, to)| !to.is_valid(width)) {
+            Some((key, to)) => Err(KeyMapError(*key, *to)),
+            None => Ok(()),
+        }
+    }
+
+    // load a key map from a RON file, every key is checked against the width
+    pub fn load<P: AsRef<path::Path>>(
+        path: P,
+        width: DataWidth,
+    ) -> Result<Self, Box<dyn error::Error>> {
+        let text = fs::read_to_string(path)?;
+        let map: Self = ron::from_str(&text)?;
+        map.check(width)?;
+        Ok(map)
+    }
+}
+
+/* Keyboard Input Entity: CompInput, PinsOut, DataOut */
 #[derive(Component)]
 pub struct CompInput;
 
-// apply computed buffer to output pins
-pub fn sys_tock(mut events: EventReader<keyboard::KeyboardInput>, mut device: ResMut<InputDevice>) {
+// set the bits of the keys pressed in the buffer, keys which are not mapped are ignored
+pub fn sys_tock(
+    mut events: EventReader<keyboard::KeyboardInput>,
+    keymap: Res<KeyMap>,
+    mut device: ResMut<InputDevice>,
+) {
     for event in events.read() {
-        // find which word and which bit of the buffer to write to
-        let code = event.scan_code as usize;
-        let word = code / NB_CHANNELS;
-        let bit = code % DATA_SIZE;
+        let Some(to) = event.key_code.and_then(|key| keymap.get(key)) else {
+            continue;
+        };
 
         // add or remove a bit from the buffer
+        let word = &mut device.buffer[to.channel as usize];
         match event.state {
-            ButtonState::Pressed => device.buffer[word] |= 1 << bit,
-            ButtonState::Released => device.buffer[word] &= !(1 << bit),
+            ButtonState::Pressed => *word |= 1 << to.bit,
+            ButtonState::Released => *word &= !(1 << to.bit),
         }
     }
 }
@@ -30,15 +158,93 @@ pub fn sys_tock(mut events: EventReader<keyboard::KeyboardInput>, mut device: Re
 // apply computed buffer to output pins
 pub fn sys_tick(
     device: Res<InputDevice>,
-    comp_query: Query<&PinsOut, With<CompInput>>,
-    mut next_query: Query<(&PinChannel, &mut DataNext)>,
+    mut comp_query: Query<(Ref<CompInput>, &PinsOut, &mut DataOut)>,
+    chan_query: Query<&PinChannel>,
 ) {
-    for pins_out in comp_query.iter() {
-        // apply the data to each output pins based on their index
-        for id in pins_out.0.iter() {
-            if let Ok((index, mut pin)) = next_query.get_mut(*id) {
-                pin.0 |= device.buffer[index.0 as usize];
-            }
+    for (comp, pins_out, mut data_out) in comp_query.iter_mut() {
+        // nothing to do if the buffer did not change since last tick
+        if !device.is_changed() && !comp.is_added() {
+            continue;
         }
+
+        // apply the data to each output pins based on their index
+        let values = pins_out
+            .0
+            .iter()
+            .map(|id| match chan_query.get(*id) {
+                Ok(index) => device.buffer[index.0 as usize],
+                Err(_) => 0,
+            })
+            .collect();
+        data_out.set_if_neq(DataOut(values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+    use crate::simulator::Simulator;
+
+    fn key(app: &mut App, key_code: KeyCode, state: ButtonState) {
+        app.world.send_event(keyboard::KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+
+    #[test]
+    fn keys_drive_their_bit() {
+        let schema = schema(
+            vec![wire(0), wire(1)],
+            vec![comp(CompType::Input, vec![], vec![0, 1])],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        let app = sim.app_mut().unwrap();
+        key(app, KeyCode::D, ButtonState::Pressed);
+        key(app, KeyCode::Left, ButtonState::Pressed);
+        key(app, KeyCode::Z, ButtonState::Pressed);
+        app.update();
+        sim.step(1);
+        assert_eq!(read_wires(&sim), vec![0b1000, 0b0010]);
+
+        let app = sim.app_mut().unwrap();
+        key(app, KeyCode::D, ButtonState::Released);
+        app.update();
+        sim.step(1);
+        assert_eq!(read_wires(&sim), vec![0, 0b0010]);
+    }
+
+    #[test]
+    fn bounds_are_checked() {
+        let path = temp_path("keys.ron");
+        let width = DataWidth(64);
+        fs::write(&path, "({A: (channel: 3, bit: 64)})").unwrap();
+        assert!(KeyMap::load(&path, width).is_err());
+        fs::write(&path, "({A: (channel: 16, bit: 0)})").unwrap();
+        assert!(KeyMap::load(&path, width).is_err());
+        fs::write(&path, "({A: (channel: 15, bit: 63)})").unwrap();
+        assert!(KeyMap::load(&path, width).is_ok());
+        // the bit must be carried by the wires of the circuit
+        assert!(KeyMap::load(&path, DataWidth(8)).is_err());
+        assert!(KeyMap::ascii().check(DataWidth(16)).is_ok());
+        assert!(KeyMap::ascii().check(DataWidth(8)).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("ascii.ron");
+        let map = KeyMap::ascii();
+        fs::write(&path, ron::to_string(&map).unwrap()).unwrap();
+        let loaded = KeyMap::load(&path, DataWidth::default()).unwrap();
+        assert_eq!(loaded, map);
+        let space = KeyBit {
+            channel: 2,
+            bit: 10,
+        };
+        assert_eq!(loaded.get(KeyCode::Space), Some(space));
     }
 }
@@ -0,0 +1,232 @@
+use once_cell::sync::Lazy;
+use std::io::{self, BufReader, Read, Write};
+use std::sync::{mpsc, Mutex};
+#[cfg(test)]
+use std::{collections::VecDeque, sync::Arc};
+use std::{fs, path, thread};
+
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
+};
+
+// streams a bus exchanges bytes with
+pub trait IoBackend: Send + Sync {
+    // next byte available, never blocks
+    fn read(&mut self) -> Option<u8>;
+    // write a single byte, a closed stream drops it
+    fn write(&mut self, byte: u8);
+}
+
+// stdin has no non-blocking read, a thread waits for the bytes instead,
+// it is started on the first read and shared by every bus
+static STDIN: Lazy<Mutex<mpsc::Receiver<u8>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            match byte {
+                Ok(byte) if sender.send(byte).is_ok() => {}
+                _ => break,
+            }
+        }
+    });
+    Mutex::new(receiver)
+});
+
+/* Standard streams of the process */
+pub struct StdioBackend;
+
+impl IoBackend for StdioBackend {
+    fn read(&mut self) -> Option<u8> {
+        STDIN.lock().ok()?.try_recv().ok()
+    }
+
+    fn write(&mut self, byte: u8) {
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+}
+
+/* Pair of files: bytes are read from the first one and written to the second one */
+pub struct FileBackend {
+    incoming: io::Bytes<BufReader<fs::File>>,
+    outgoing: fs::File,
+}
+
+impl FileBackend {
+    // the output file is truncated
+    pub fn open<P: AsRef<path::Path>>(input: P, output: P) -> io::Result<Self> {
+        Ok(Self {
+            incoming: BufReader::new(fs::File::open(input)?).bytes(),
+            outgoing: fs::File::create(output)?,
+        })
+    }
+}
+
+impl IoBackend for FileBackend {
+    fn read(&mut self) -> Option<u8> {
+        self.incoming.next()?.ok()
+    }
+
+    fn write(&mut self, byte: u8) {
+        let _ = self.outgoing.write_all(&[byte]);
+    }
+}
+
+/* Queues in memory, clones share the same queues, for the tests */
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryBackend(Arc<Mutex<MemoryQueues>>);
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryQueues {
+    incoming: VecDeque<u8>,
+    outgoing: Vec<u8>,
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    // queue bytes to be read by the bus
+    pub fn push(&self, bytes: &[u8]) {
+        if let Ok(mut queues) = self.0.lock() {
+            queues.incoming.extend(bytes);
+        }
+    }
+
+    // remove the bytes written by the bus
+    pub fn take(&self) -> Vec<u8> {
+        match self.0.lock() {
+            Ok(mut queues) => std::mem::take(&mut queues.outgoing),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl IoBackend for MemoryBackend {
+    fn read(&mut self) -> Option<u8> {
+        self.0.lock().ok()?.incoming.pop_front()
+    }
+
+    fn write(&mut self, byte: u8) {
+        if let Ok(mut queues) = self.0.lock() {
+            queues.outgoing.push(byte);
+        }
+    }
+}
+
+/* Unix domain socket another local process connects to, one peer at a time */
+#[cfg(unix)]
+pub struct SocketBackend {
+    listener: UnixListener,
+    stream: Option<UnixStream>,
+    path: path::PathBuf,
+}
+
+#[cfg(unix)]
+impl SocketBackend {
+    // listen on the given path, a socket left by a previous run is replaced
+    // but any other file is kept
+    pub fn bind<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => fs::remove_file(&path)?,
+            Ok(_) => return Err(io::ErrorKind::AlreadyExists.into()),
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            stream: None,
+            path,
+        })
+    }
+
+    // stream of the peer, accept a new one if there is none
+    fn peer(&mut self) -> Option<&mut UnixStream> {
+        if self.stream.is_none() {
+            let (stream, _) = self.listener.accept().ok()?;
+            stream.set_nonblocking(true).ok()?;
+            self.stream = Some(stream);
+        }
+        self.stream.as_mut()
+    }
+}
+
+#[cfg(unix)]
+impl IoBackend for SocketBackend {
+    fn read(&mut self) -> Option<u8> {
+        let mut buffer = [0u8];
+        match self.peer()?.read(&mut buffer) {
+            Ok(1) => Some(buffer[0]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
+            // the peer left, wait for the next one
+            _ => {
+                self.stream = None;
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, byte: u8) {
+        if let Some(stream) = self.peer() {
+            match stream.write_all(&[byte]) {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => self.stream = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SocketBackend {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+
+    #[test]
+    fn files() {
+        let input = temp_path("bus.in");
+        let output = temp_path("bus.out");
+        fs::write(&input, "ab").unwrap();
+        let mut backend = FileBackend::open(&*input, &*output).unwrap();
+        assert_eq!(backend.read(), Some(b'a'));
+        assert_eq!(backend.read(), Some(b'b'));
+        assert_eq!(backend.read(), None);
+        backend.write(b'z');
+        assert_eq!(fs::read(&output).unwrap(), b"z");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket() {
+        let path = temp_path("bus.sock");
+        let mut backend = SocketBackend::bind(&*path).unwrap();
+        assert_eq!(backend.read(), None);
+        let mut peer = UnixStream::connect(&*path).unwrap();
+        peer.write_all(b"x").unwrap();
+        assert_eq!(backend.read(), Some(b'x'));
+        backend.write(b'y');
+        let mut buffer = [0u8];
+        peer.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"y");
+
+        // a socket left behind is replaced, not any other file
+        std::mem::forget(backend);
+        assert!(SocketBackend::bind(&*path).is_ok());
+        let file = temp_path("bus.txt");
+        fs::write(&file, "keep").unwrap();
+        assert!(SocketBackend::bind(&*file).is_err());
+        assert_eq!(fs::read(&file).unwrap(), b"keep");
+    }
+}
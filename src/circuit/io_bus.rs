@@ -1,30 +1,250 @@
 use super::*;
+use serde::{Deserialize, Serialize};
 
-/* IO Bus Entity: CompBus, PinsIn, PinsOut */
-#[derive(Component)]
-pub struct CompIOBus;
+/* IO Bus Device: backends the buses exchange bytes with, indexed by port */
+// a disconnected port reads nothing and drops the bytes written
+#[derive(Default, Resource)]
+pub struct IOBusDevice {
+    ports: Vec<Option<Box<dyn IoBackend>>>,
+}
 
-// combine multiple input values as boolean into a single wire
-pub fn sys_tick(
-    comp_query: Query<(&PinsIn, &PinsOut), With<CompIOBus>>,
-    prev_query: Query<(&PinChannel, &DataPrev)>,
-    mut next_query: Query<(&PinChannel, &mut DataNext)>,
-) {
-    for (pins_in, pins_out) in comp_query.iter() {
-        // prepare stdin to read from and stdout to write to
+impl IOBusDevice {
+    pub fn new(ports: Vec<Box<dyn IoBackend>>) -> Self {
+        Self {
+            ports: ports.into_iter().map(Some).collect(),
+        }
+    }
 
-        // write input pins data to stdout
-        for id in pins_in.0.iter() {
-            if let Ok((_index, _pin)) = prev_query.get(*id) {
-                // TODO
+    // ports which are not connected to any backend yet
+    pub fn disconnected(nb_ports: usize) -> Self {
+        Self {
+            ports: (0..nb_ports).map(|_| None).collect(),
+        }
+    }
+
+    // replace the backend of a port, return false if there is no such port
+    pub fn set(&mut self, port: usize, backend: Box<dyn IoBackend>) -> bool {
+        match self.ports.get_mut(port) {
+            Some(slot) => {
+                *slot = Some(backend);
+                true
             }
+            None => false,
+        }
+    }
+
+    // next byte available on the port, never blocks
+    pub fn read(&mut self, port: usize) -> Option<u8> {
+        self.ports.get_mut(port)?.as_mut()?.read()
+    }
+
+    // write a single byte on the port
+    pub fn write(&mut self, port: usize, byte: u8) {
+        if let Some(Some(backend)) = self.ports.get_mut(port) {
+            backend.write(byte);
         }
+    }
+}
+
+/* IO Bus Entity: CompIOBus, PinsIn, PinsOut, DataOut */
+// input wires on the valid channel write the byte on a rising edge,
+// output wires on the valid channel are on while a byte read is available
+pub const BUS_VALID: Channel = 0;
+// input wires on the ready channel acknowledge the byte read on a rising edge,
+// the next byte is then fetched
+pub const BUS_READY: Channel = 1;
+// wires on the eight channels from this one carry the bits of the byte, lowest first
+pub const BUS_DATA: Channel = 8;
 
-        // read stdin data and write it to output wires
-        for id in pins_out.0.iter() {
-            if let Ok((_index, mut _pin)) = next_query.get_mut(*id) {
-                // TODO
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompIOBus {
+    // port of the device the bus is connected to
+    pub port: usize,
+    // byte read waiting to be acknowledged
+    pub byte: Option<u8>,
+    pub valid: bool,
+    pub ready: bool,
+}
+
+impl CompIOBus {
+    pub fn new(port: usize) -> Self {
+        Self {
+            port,
+            ..Default::default()
+        }
+    }
+
+    // exchange bytes with the port of the device from the input wires
+    pub fn update(&mut self, inputs: &ByChannel, device: &mut IOBusDevice) {
+        let valid = inputs[BUS_VALID as usize].unwrap_or(0) != 0;
+        let ready = inputs[BUS_READY as usize].unwrap_or(0) != 0;
+
+        // pack the data channels into a byte
+        if valid && !self.valid {
+            let byte = (0..8).fold(0u8, |byte, bit| {
+                let on = inputs[(BUS_DATA + bit) as usize].unwrap_or(0) != 0;
+                byte | (on as u8) << bit
+            });
+            device.write(self.port, byte);
+        }
+        if ready && !self.ready {
+            self.byte = None;
+        }
+        if self.byte.is_none() {
+            self.byte = device.read(self.port);
+        }
+        self.valid = valid;
+        self.ready = ready;
+    }
+
+    // data to drive on output wires of the given channel
+    pub fn output(&self, channel: Channel) -> Data {
+        match (channel, self.byte) {
+            (BUS_VALID, byte) => data_from_bool(byte.is_some()),
+            (c, Some(byte)) if (BUS_DATA..BUS_DATA + 8).contains(&c) => {
+                data_from_bool((byte >> (c - BUS_DATA)) & 1 == 1)
             }
+            _ => 0,
         }
     }
 }
+
+// exchange bytes between the wires and the device
+pub fn sys_tick(
+    mut device: ResMut<IOBusDevice>,
+    mut comp_query: Query<(&mut CompIOBus, &PinsIn, &PinsOut, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+    chan_query: Query<&PinChannel>,
+) {
+    // the bus talks to the outside world, so it is evaluated on every tick
+    for (mut bus, pins_in, pins_out, mut data_out) in comp_query.iter_mut() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        bus.update(&inputs, &mut device);
+
+        // apply the byte read to the output wires based on their index
+        let values = pins_out
+            .0
+            .iter()
+            .map(|id| match chan_query.get(*id) {
+                Ok(index) => bus.output(index.0),
+                Err(_) => 0,
+            })
+            .collect();
+        data_out.set_if_neq(DataOut(values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{BusBackend, CompType, Error, Index};
+    use crate::simulator::Simulator;
+
+    fn device(bytes: &[u8]) -> (IOBusDevice, MemoryBackend) {
+        let backend = MemoryBackend::default();
+        backend.push(bytes);
+        (IOBusDevice::new(vec![Box::new(backend.clone())]), backend)
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut device, backend) = device(b"hi");
+        let mut bus = CompIOBus::default();
+        let mut inputs: ByChannel = [None; NB_CHANNELS];
+        bus.update(&inputs, &mut device);
+        assert_eq!(bus.output(BUS_VALID), Data::MAX);
+        assert_eq!(bus.output(BUS_DATA + 3), Data::MAX);
+        assert_eq!(bus.output(BUS_DATA), 0);
+
+        // the byte is kept until acknowledged
+        bus.update(&inputs, &mut device);
+        assert_eq!(bus.byte, Some(b'h'));
+        inputs[BUS_READY as usize] = Some(1);
+        bus.update(&inputs, &mut device);
+        assert_eq!(bus.byte, Some(b'i'));
+        bus.update(&inputs, &mut device);
+        assert_eq!(bus.byte, Some(b'i'));
+        inputs[BUS_READY as usize] = Some(0);
+        bus.update(&inputs, &mut device);
+        inputs[BUS_READY as usize] = Some(1);
+        bus.update(&inputs, &mut device);
+        assert_eq!(bus.output(BUS_VALID), 0);
+
+        // written once on the rising edge
+        inputs[BUS_DATA as usize] = Some(1);
+        inputs[(BUS_DATA + 5) as usize] = Some(4);
+        inputs[BUS_VALID as usize] = Some(1);
+        bus.update(&inputs, &mut device);
+        bus.update(&inputs, &mut device);
+        assert_eq!(backend.take(), b"!");
+    }
+
+    #[test]
+    fn echo() {
+        // the byte read is written back as soon as it is valid
+        let mut wires = vec![wire(BUS_VALID)];
+        wires.extend((0..8).map(|bit| wire(BUS_DATA + bit)));
+        let pins: Vec<Index> = (0..9).collect();
+        let bus = CompType::Bus(BusBackend::default());
+        let schema = schema(wires, vec![comp(bus, pins.clone(), pins)]);
+        on_every_backend(&schema, |sim| {
+            let backend = MemoryBackend::default();
+            backend.push(b"A");
+            assert!(sim.set_bus_backend(0, Box::new(backend.clone())));
+            assert!(!sim.set_bus_backend(1, Box::new(backend.clone())));
+            sim.step(4);
+            let written = backend.take();
+            assert_eq!(written, b"A");
+            written
+        });
+    }
+
+    #[test]
+    fn missing_files() {
+        let input = temp_path("missing.in");
+        let output = temp_path("bus.out");
+        let nowhere = temp_path("missing").to_string() + "/bus.out";
+        let bus = |input: &str, output: &str| {
+            let backend = BusBackend::Files(input.to_string(), output.to_string());
+            comp(CompType::Bus(backend), vec![], vec![0])
+        };
+        let schema = schema(
+            vec![wire(0)],
+            vec![bus(&input, &output), bus(&output, &nowhere)],
+        );
+        std::fs::write(&output, "").unwrap();
+        let errors = schema.verify().unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [Error::BusBackend(0), Error::BusBackend(1)]
+        ));
+    }
+
+    #[test]
+    fn disconnected_until_opened() {
+        let input = temp_path("opened.in");
+        let output = temp_path("opened.out");
+        std::fs::write(&input, "A").unwrap();
+        let backend = BusBackend::Files(input.to_string(), output.to_string());
+        let mut wires = vec![wire(BUS_VALID)];
+        wires.extend((0..8).map(|bit| wire(BUS_DATA + bit)));
+        let pins: Vec<Index> = (0..9).collect();
+        let schema = schema(
+            wires,
+            vec![comp(CompType::Bus(backend), pins.clone(), pins)],
+        );
+
+        // building the circuit does not touch the files
+        let mut sim = Simulator::new(schema.clone()).unwrap();
+        assert!(!std::path::Path::new(&*output).exists());
+        sim.step(4);
+        assert!(!std::path::Path::new(&*output).exists());
+
+        // the echo goes through the files once they are opened
+        let mut backends = schema.prepare().unwrap().open_buses().unwrap();
+        assert!(sim.set_bus_backend(0, backends.remove(0)));
+        sim.step(4);
+        assert_eq!(std::fs::read(&output).unwrap(), b"A");
+    }
+}
@@ -0,0 +1,57 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/* SR Latch Entity: CompLatch, PinsIn, PinsOut, DataOut */
+// input wires on the set channel turn the latch on
+pub const LATCH_SET: Channel = 0;
+// input wires on the reset channel turn the latch off, reset wins over set
+pub const LATCH_RESET: Channel = 1;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompLatch {
+    pub state: bool,
+}
+
+impl CompLatch {
+    // update the state from the input wires, return the data to output
+    pub fn update(&mut self, inputs: &ByChannel) -> Data {
+        if inputs[LATCH_RESET as usize].unwrap_or(0) != 0 {
+            self.state = false;
+        } else if inputs[LATCH_SET as usize].unwrap_or(0) != 0 {
+            self.state = true;
+        }
+        data_from_bool(self.state)
+    }
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut CompLatch, &PinsIn, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut latch, pins_in, mut data_out)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        let data = latch.update(&inputs);
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_wins_over_set() {
+        let mut latch = CompLatch::default();
+        let mut inputs: ByChannel = [None; NB_CHANNELS];
+        assert_eq!(latch.update(&inputs), 0);
+        inputs[LATCH_SET as usize] = Some(1);
+        assert_eq!(latch.update(&inputs), Data::MAX);
+        inputs[LATCH_SET as usize] = Some(0);
+        assert_eq!(latch.update(&inputs), Data::MAX);
+        inputs[LATCH_SET as usize] = Some(1);
+        inputs[LATCH_RESET as usize] = Some(1);
+        assert_eq!(latch.update(&inputs), 0);
+    }
+}
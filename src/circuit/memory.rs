@@ -0,0 +1,288 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::{fs, io, path};
+
+/* Memory Entity (RAM/ROM): CompMemory, PinsIn, PinsOut, DataOut */
+// input wires on the address channel select the word to read and write
+pub const MEMORY_ADDRESS: Channel = 0;
+// input wires on the write channel store the data when not null
+pub const MEMORY_WRITE: Channel = 1;
+// input wires on any other channel carry the data to write
+// memories hold at most this amount of words
+pub const MEMORY_MAX_SIZE: usize = 1 << 20;
+
+#[derive(Component, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompMemory {
+    pub words: Vec<Data>,
+    // read-only memories ignore the write channel
+    pub writable: bool,
+}
+
+impl CompMemory {
+    // allocate a memory filled with the image, the rest is zero,
+    // the words of the image past the size are dropped
+    pub fn new(size: usize, writable: bool, image: &[Data]) -> Self {
+        let mut words = vec![0; size];
+        let len = size.min(image.len());
+        words[..len].copy_from_slice(&image[..len]);
+        Self { words, writable }
+    }
+
+    // address of the word read and written given the input wires
+    pub fn address(inputs: &ByChannel) -> usize {
+        inputs[MEMORY_ADDRESS as usize].unwrap_or(0) as usize
+    }
+
+    // update the memory from the input wires, return the word at the address
+    pub fn update(&mut self, inputs: &ByChannel) -> Data {
+        let address = Self::address(inputs);
+        let write = inputs[MEMORY_WRITE as usize].unwrap_or(0) != 0;
+
+        // out of range addresses read zero and ignore writes
+        let Some(word) = self.words.get_mut(address) else {
+            return 0;
+        };
+        if write && self.writable {
+            *word = inputs
+                .iter()
+                .enumerate()
+                .filter(|(channel, _)| {
+                    *channel != MEMORY_ADDRESS as usize && *channel != MEMORY_WRITE as usize
+                })
+                .fold(0, |data, (_, value)| data | value.unwrap_or(0));
+        }
+        *word
+    }
+}
+
+// load the words of a memory, hex files contain words separated by whitespaces,
+// any other file contains words of the bytes of the data width in little endian
+pub fn load_image<P: AsRef<path::Path>>(path: P, width: DataWidth) -> io::Result<Vec<Data>> {
+    let mut words = Vec::new();
+    read_image(path.as_ref(), width, |word| words.push(word))?;
+    Ok(words)
+}
+
+// read the words of an image one by one, words wider than the data width are refused
+fn read_image(path: &path::Path, width: DataWidth, mut f: impl FnMut(Data)) -> io::Result<()> {
+    if is_hex(path) {
+        let text = fs::read_to_string(path)?;
+        for token in text.split_whitespace() {
+            let word = Data::from_str_radix(token, 16)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if word & !width.mask() != 0 {
+                let error = format!("word {} wider than {} bits", token, width.0);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+            }
+            f(word);
+        }
+    } else {
+        // the last word may be shorter than the others, the bits past the data width
+        // of the last byte are dropped
+        let size = width.bytes();
+        let mut reader = io::BufReader::new(fs::File::open(path)?);
+        let mut chunk = Vec::with_capacity(size);
+        loop {
+            chunk.clear();
+            (&mut reader).take(size as u64).read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                break;
+            }
+            let mut buffer = [0u8; std::mem::size_of::<Data>()];
+            buffer[..chunk.len()].copy_from_slice(&chunk);
+            f(Data::from_le_bytes(buffer) & width.mask());
+        }
+    }
+    Ok(())
+}
+
+// save the words of a memory in the same formats as `load_image`
+pub fn save_image<P: AsRef<path::Path>>(
+    path: P,
+    words: &[Data],
+    width: DataWidth,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let size = width.bytes();
+    if is_hex(path) {
+        let text: Vec<String> = words
+            .iter()
+            .map(|word| format!("{:0digits$x}", word, digits = size * 2))
+            .collect();
+        fs::write(path, text.join("\n"))
+    } else {
+        let bytes: Vec<u8> = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().into_iter().take(size))
+            .collect();
+        fs::write(path, bytes)
+    }
+}
+
+fn is_hex(path: &path::Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "hex")
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut CompMemory, &PinsIn, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut memory, pins_in, mut data_out)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        let data = memory.update(&inputs);
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompType, Error, MemoryParams, Schema, Warning};
+    use crate::simulator::Simulator;
+
+    fn memory(size: u32, writable: bool, image: Option<String>) -> CompType {
+        CompType::Memory(MemoryParams {
+            size,
+            writable,
+            image,
+        })
+    }
+
+    #[test]
+    fn read_and_write() {
+        let rom = temp_path("rom.hex");
+        fs::write(&rom, "0001 0002\n00ff").unwrap();
+        let schema = schema(
+            vec![wire(0), wire(1), wire(2), wire(3), wire(4)],
+            vec![
+                comp(CompType::Input, vec![], vec![0, 1, 2]),
+                comp(
+                    memory(0, false, Some(rom.to_string())),
+                    vec![0, 1, 2],
+                    vec![3],
+                ),
+                comp(memory(4, true, None), vec![0, 1, 2], vec![4]),
+            ],
+        );
+        on_every_backend(&schema, |sim| {
+            sim.write_input(MEMORY_ADDRESS, 2);
+            sim.step(3);
+            assert_eq!(sim.read_wire(3), Some(0xff));
+            // the rom ignores the write
+            sim.write_input(MEMORY_WRITE, 1);
+            sim.write_input(2, 77);
+            sim.step(3);
+            assert_eq!(sim.read_wire(3), Some(0xff));
+            assert_eq!(sim.read_wire(4), Some(77));
+            assert_eq!(sim.dump_memory(1), Some(vec![1, 2, 0xff]));
+            assert_eq!(sim.dump_memory(2), Some(vec![0, 0, 77, 0]));
+            assert_eq!(sim.dump_memory(0), None);
+            let dump = temp_path("dump.bin");
+            sim.save_memory(2, &dump).unwrap();
+            let words = load_image(&dump, DataWidth::default()).unwrap();
+            assert_eq!(words, vec![0, 0, 77, 0]);
+            assert!(sim.save_memory(0, &dump).is_err());
+        });
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let path = temp_path("ram.bin");
+        let width = DataWidth::default();
+        save_image(&path, &[0, 0x1234, 77], width).unwrap();
+        assert_eq!(load_image(&path, width).unwrap(), vec![0, 0x1234, 77]);
+
+        // words take the bytes of the data width
+        for (bits, words) in [(8, vec![0xff, 3]), (64, vec![Data::MAX, 1 << 40])] {
+            let width = DataWidth(bits);
+            save_image(&path, &words, width).unwrap();
+            let len = fs::metadata(&path).unwrap().len() as usize;
+            assert_eq!(len, words.len() * width.bytes());
+            assert_eq!(load_image(&path, width).unwrap(), words);
+        }
+        let hex = temp_path("byte.hex");
+        fs::write(&hex, "ff 100").unwrap();
+        assert!(load_image(&hex, DataWidth(8)).is_err());
+    }
+
+    #[test]
+    fn missing_image() {
+        let image = temp_path("missing.hex");
+        let schema = schema(
+            vec![wire(0)],
+            vec![comp(
+                memory(0, false, Some(image.to_string())),
+                vec![],
+                vec![0],
+            )],
+        );
+        assert!(Simulator::new(schema).is_err());
+    }
+
+    #[test]
+    fn image_next_to_schematic() {
+        // relative paths start from the directory of the schematic file
+        let rom = temp_path("relative.hex");
+        fs::write(&rom, "5 6").unwrap();
+        let file = path::Path::new(&*rom).file_name().unwrap();
+        let image = Some(file.to_string_lossy().into_owned());
+        let path = temp_path("relative.blc");
+        schema(
+            vec![wire(0)],
+            vec![comp(memory(0, false, image), vec![], vec![0])],
+        )
+        .save(&path)
+        .unwrap();
+        let sim = Simulator::new(Schema::load(&path).unwrap()).unwrap();
+        assert_eq!(sim.dump_memory(0), Some(vec![5, 6]));
+    }
+
+    #[test]
+    fn rejects_invalid_memories() {
+        let empty = temp_path("empty.hex");
+        fs::write(&empty, "").unwrap();
+        let wide = temp_path("wide.hex");
+        fs::write(&wide, "1 10000").unwrap();
+        let schema = schema(
+            vec![wire(0)],
+            vec![
+                comp(memory(0, true, None), vec![], vec![0]),
+                comp(memory(0, false, Some(empty.to_string())), vec![], vec![0]),
+                comp(memory(4, false, Some(wide.to_string())), vec![], vec![0]),
+                comp(memory(u32::MAX, true, None), vec![], vec![0]),
+            ],
+        );
+        let errors = schema.verify().unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                Error::MemorySize(0),
+                Error::MemorySize(1),
+                Error::MemoryImage(2),
+                Error::MemorySize(3)
+            ]
+        ));
+    }
+
+    #[test]
+    fn warns_about_truncated_images() {
+        let rom = temp_path("long.hex");
+        fs::write(&rom, "1 2 3 4 5").unwrap();
+        let schema = schema(
+            vec![wire(0)],
+            vec![comp(
+                memory(3, false, Some(rom.to_string())),
+                vec![],
+                vec![0],
+            )],
+        );
+        assert_eq!(schema.verify().unwrap(), vec![Warning::MemoryImage(0, 2)]);
+        let mut sim = Simulator::new(schema).unwrap();
+        sim.step(1);
+        assert_eq!(sim.dump_memory(0), Some(vec![1, 2, 3]));
+    }
+}
@@ -1,24 +1,54 @@
 /**
  * Plugin for running logic circuits
  */
+use crate::schematic::SchemaWires;
+use crate::snapshot::{self, History};
+use crate::trace::{self, BreakpointHit, Breakpoints, CycleDetector, TraceRecorder};
 use bevy::prelude::*;
+use std::collections::HashMap;
 
 mod base;
+mod clock;
+mod delay;
 mod demux;
+mod display;
 mod fixed;
 mod gate;
 mod input;
+mod io_backend;
 mod io_bus;
+mod latch;
+mod memory;
 mod mux;
+mod register;
+mod schedule;
+mod settle;
+mod toggle;
+mod tristate;
 
 // types to export
 pub use base::*;
+pub use clock::{ClockParams, CompClock};
+pub use delay::{Pipeline, MAX_DELAY};
 pub use demux::CompDemux;
+pub use display::DisplayState;
 pub use fixed::CompFixed;
-pub use gate::Operator;
-pub use input::{CompInput, InputDevice};
-pub use io_bus::CompIOBus;
+pub use gate::{Operator, OverflowMode};
+pub use input::{CompInput, InputDevice, KeyMap};
+#[cfg(test)]
+pub use io_backend::MemoryBackend;
+#[cfg(unix)]
+pub use io_backend::SocketBackend;
+pub use io_backend::{FileBackend, IoBackend, StdioBackend};
+pub use io_bus::{CompIOBus, IOBusDevice};
+pub use latch::CompLatch;
+pub use memory::{load_image, save_image, CompMemory, MEMORY_MAX_SIZE};
 pub use mux::CompMux;
+pub use register::CompRegister;
+pub use schedule::{CircuitSet, CircuitTick, SimState, TickCount, TickRate, TickScheduler};
+pub use settle::{CircuitSettle, Oscillation, Settling, TimingMode, EXTRA_PASSES};
+pub use toggle::CompToggle;
+pub use tristate::CompTriState;
 
 // plugin for running the circuit
 pub struct CircuitPlugin;
@@ -27,29 +57,508 @@ impl Plugin for CircuitPlugin {
     fn build(&self, app: &mut App) {
         app
             // add singleton components as resources
+            .insert_resource(DataWidth::default())
             .insert_resource(InputDevice::default())
-            // reset before next tick
-            .add_systems(PreUpdate, (sys_tock, input::sys_tock))
-            // tick update
+            .insert_resource(KeyMap::default())
+            .insert_resource(IOBusDevice::default())
+            .insert_resource(DirtyComps::default())
+            .insert_resource(DirtyAll::default())
+            .insert_resource(WireConflicts::default())
+            .insert_resource(TimingMode::default())
+            .insert_resource(Settling::default())
+            .insert_resource(TickScheduler::default())
+            .insert_resource(TickCount::default())
+            .add_state::<SimState>()
+            .add_event::<BreakpointHit>()
+            .add_event::<DriverConflict>()
+            .add_event::<Oscillation>()
+            // read the keyboard once per frame
+            .add_systems(PreUpdate, input::sys_tock)
+            // run the ticks required for this frame, pausing, stepping or going back first
+            // if asked to
             .add_systems(
                 Update,
                 (
-                    io_bus::sys_tick,
+                    schedule::sys_keys,
+                    snapshot::sys_step_back.run_if(resource_exists::<History>()),
+                    schedule::sys_run,
+                )
+                    .chain(),
+            )
+            // steps of a tick, see `CircuitSet` for the contract of each step
+            .configure_sets(
+                CircuitTick,
+                (
+                    CircuitSet::Latch,
+                    CircuitSet::Input,
+                    CircuitSet::Evaluate,
+                    CircuitSet::Output,
+                )
+                    .chain(),
+            )
+            // reset before next tick
+            .add_systems(
+                CircuitTick,
+                (sys_tock, sys_mark).chain().in_set(CircuitSet::Latch),
+            )
+            // devices feeding the circuit
+            .add_systems(
+                CircuitTick,
+                (input::sys_tick, io_bus::sys_tick).in_set(CircuitSet::Input),
+            )
+            // tick update, only components with changed inputs are evaluated
+            .add_systems(
+                CircuitTick,
+                (
                     gate::sys_tick,
                     fixed::sys_tick,
-                    input::sys_tick,
                     mux::sys_tick,
                     demux::sys_tick,
-                ),
+                    register::sys_tick,
+                    latch::sys_tick,
+                    toggle::sys_tick,
+                    tristate::sys_tick,
+                    memory::sys_tick,
+                    display::sys_tick,
+                    clock::sys_tick,
+                )
+                    .in_set(CircuitSet::Evaluate),
+            )
+            // move the data of delayed components then combine the drivers of each wire,
+            // the combinational logic settles there when required, before outputs are read
+            // and conflicts reported
+            .add_systems(
+                CircuitTick,
+                (
+                    delay::sys_tick,
+                    sys_resolve,
+                    settle::sys_settle.run_if(resource_equals(TimingMode::Settle)),
+                    sys_conflicts,
+                )
+                    .chain()
+                    .after(CircuitSet::Evaluate)
+                    .before(CircuitSet::Output),
+            )
+            // a single pass over the combinational components while settling
+            .add_systems(
+                CircuitSettle,
+                (
+                    settle::sys_tock,
+                    sys_mark,
+                    settle::sys_keep,
+                    (
+                        gate::sys_tick,
+                        mux::sys_tick,
+                        demux::sys_tick,
+                        tristate::sys_tick,
+                    ),
+                    sys_resolve,
+                )
+                    .chain(),
+            )
+            // sample the wires once a trace recorder is inserted
+            .add_systems(
+                CircuitTick,
+                trace::sys_record
+                    .run_if(resource_exists::<TraceRecorder>())
+                    .in_set(CircuitSet::Output),
+            )
+            // pause once a breakpoint is inserted and fires
+            .add_systems(
+                CircuitTick,
+                trace::sys_check
+                    .run_if(resource_exists::<Breakpoints>())
+                    .in_set(CircuitSet::Output),
+            )
+            // the hits are dropped when the circuit resumes
+            .add_systems(
+                OnEnter(SimState::Running),
+                trace::sys_resume.run_if(resource_exists::<Breakpoints>()),
+            )
+            // watch the wires cycling once a detector is inserted
+            .add_systems(
+                CircuitTick,
+                trace::sys_detect
+                    .run_if(resource_exists::<CycleDetector>())
+                    .in_set(CircuitSet::Output),
+            )
+            // keep the changes of each tick once a history is inserted
+            .add_systems(
+                CircuitTick,
+                snapshot::sys_history
+                    .run_if(resource_exists::<History>())
+                    .in_set(CircuitSet::Output),
             );
     }
 }
 
-/* Wire Entity: PinChannel, DataPrev, DataNext */
-// reset the state of every wire
-fn sys_tock(mut query: Query<(&mut DataPrev, &mut DataNext)>) {
-    query.for_each_mut(|(mut wire_prev, mut wire_next)| {
-        wire_prev.0 = wire_next.0;
-        wire_next.0 = 0;
+/* Wire Entity: PinChannel, DataPrev, DataNext, Fanout, Drivers */
+// move the state of every wire to the next tick,
+// only flag the wires which value actually changed
+fn sys_tock(mut count: ResMut<TickCount>, mut query: Query<(&mut DataPrev, &DataNext)>) {
+    count.0 += 1;
+    query.for_each_mut(|(mut wire_prev, wire_next)| {
+        wire_prev.set_if_neq(DataPrev(wire_next.0));
     });
 }
+
+// find the components reading from the wires that changed,
+// new components are evaluated at least once
+fn sys_mark(
+    mut dirty: ResMut<DirtyComps>,
+    dirty_all: Res<DirtyAll>,
+    wire_query: Query<&Fanout, Changed<DataPrev>>,
+    comp_query: Query<Entity, Added<DataOut>>,
+    all_query: Query<Entity, With<PinsIn>>,
+) {
+    dirty.0.clear();
+    // components without inputs are evaluated regardless of the wires
+    if dirty_all.0 {
+        dirty.0.extend(all_query.iter());
+        return;
+    }
+    for fanout in wire_query.iter() {
+        dirty.0.extend_from_slice(&fanout.0);
+    }
+    dirty.0.extend(comp_query.iter());
+    dirty.0.sort_unstable();
+    dirty.0.dedup();
+}
+
+// components which data driven on their output pins changed, or which let go of them
+type DrivenChanged = Or<(Changed<DataOut>, Changed<Pipeline>, Changed<CompTriState>)>;
+
+// data driven by a component on its output pins
+type DrivenData = (
+    &'static DataOut,
+    Option<&'static Pipeline>,
+    Option<&'static CompTriState>,
+);
+
+// find the wires which drivers changed since the system last ran, or every wire
+fn changed_wires(
+    dirty_all: bool,
+    schema_wires: &SchemaWires,
+    comp_query: &Query<&PinsOut, DrivenChanged>,
+) -> Vec<Entity> {
+    if dirty_all {
+        return schema_wires.0.clone();
+    }
+    let mut wires = Vec::<Entity>::new();
+    for pins_out in comp_query.iter() {
+        wires.extend_from_slice(&pins_out.0);
+    }
+    wires.sort_unstable();
+    wires.dedup();
+    wires
+}
+
+// gather the data driven on the wire, delayed components drive the data leaving
+// their pipeline, disabled tri-state buffers drive nothing
+fn read_drivers(
+    drivers: &Drivers,
+    out_query: &Query<DrivenData>,
+    width: DataWidth,
+    values: &mut Vec<Data>,
+) {
+    values.clear();
+    for (comp, pin) in drivers.0.iter() {
+        if let Ok((data_out, pipeline, tristate)) = out_query.get(*comp) {
+            if tristate.is_some_and(|tristate| !tristate.enabled) {
+                continue;
+            }
+            let driven = pipeline.map_or(&data_out.0[..], Pipeline::driven);
+            values.push(driven[*pin] & width.mask());
+        }
+    }
+}
+
+// combine the data driven by each component on the wires it writes to,
+// only the bits of the data width are kept
+fn sys_resolve(
+    width: Res<DataWidth>,
+    schema_wires: Res<SchemaWires>,
+    dirty_all: Res<DirtyAll>,
+    comp_query: Query<&PinsOut, DrivenChanged>,
+    out_query: Query<DrivenData>,
+    mut wire_query: Query<(&Drivers, &Resolution, &mut DataNext)>,
+) {
+    let wires = changed_wires(dirty_all.0, &schema_wires, &comp_query);
+    let mut values = Vec::<Data>::new();
+    let mut iter = wire_query.iter_many_mut(&wires);
+    while let Some((drivers, resolution, mut wire_next)) = iter.fetch_next() {
+        read_drivers(drivers, &out_query, *width, &mut values);
+        wire_next.0 = resolution.resolve(&values);
+    }
+}
+
+// report the strict wires which drivers disagree once the data of the tick is final,
+// only new conflicts are reported
+#[allow(clippy::too_many_arguments)]
+fn sys_conflicts(
+    width: Res<DataWidth>,
+    count: Res<TickCount>,
+    schema_wires: Res<SchemaWires>,
+    mut indices: Local<HashMap<Entity, Index>>,
+    mut dirty_all: ResMut<DirtyAll>,
+    mut conflicts: ResMut<WireConflicts>,
+    mut events: EventWriter<DriverConflict>,
+    comp_query: Query<&PinsOut, DrivenChanged>,
+    out_query: Query<DrivenData>,
+    wire_query: Query<(Entity, &Drivers, &Resolution)>,
+) {
+    let all = std::mem::take(&mut dirty_all.0);
+    let wires = changed_wires(all, &schema_wires, &comp_query);
+
+    // index of the wires in the schematic, built on the first conflict of a circuit
+    if schema_wires.is_changed() {
+        indices.clear();
+    }
+
+    let mut values = Vec::<Data>::new();
+    for (id, drivers, resolution) in wire_query.iter_many(&wires) {
+        if *resolution != Resolution::Strict {
+            continue;
+        }
+        read_drivers(drivers, &out_query, *width, &mut values);
+        if !resolution.conflicts(&values) {
+            conflicts.0.remove(&id);
+        } else if conflicts.0.get(&id) != Some(&values) {
+            conflicts.0.insert(id, values.clone());
+            if indices.is_empty() {
+                let wires = schema_wires.0.iter().enumerate();
+                *indices = wires.map(|(i, w)| (*w, i as Index)).collect();
+            }
+            events.send(DriverConflict {
+                wire: indices.get(&id).copied().unwrap_or(0),
+                tick: count.0,
+                values: values.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompType, Error, Schema, SchemaWire, Warning};
+    use crate::simulator::Simulator;
+
+    // naive evaluation of every component at every tick, with the semantics of the
+    // original circuit plugin
+    fn reference(schema: &Schema, input: &[Data; NB_CHANNELS], ticks: usize) -> Vec<Data> {
+        let mask = DataWidth::default().mask();
+        let wires = schema.wires();
+        let mut next = vec![0; wires.len()];
+        for _ in 0..ticks {
+            let prev = std::mem::replace(&mut next, vec![0; wires.len()]);
+            for comp in schema.comps().iter() {
+                let ins: Vec<Data> = comp.pins_in.iter().map(|i| prev[*i as usize]).collect();
+                let any = ins.iter().fold(0, |d, v| d | v);
+                let all = ins.iter().fold(mask, |d, v| d & v);
+                for pin in comp.pins_out.iter() {
+                    let channel = wires[*pin as usize].channel;
+                    let data = match comp.comp_type {
+                        CompType::Gate(Operator::Or, _) => any,
+                        CompType::Gate(Operator::And, _) => all,
+                        CompType::Gate(Operator::Nor, _) => !any,
+                        CompType::Gate(Operator::Nand, _) => !all,
+                        CompType::Gate(Operator::Min, _) => ins.iter().fold(mask, |d, v| d.min(*v)),
+                        CompType::Gate(Operator::Max, _) => ins.iter().fold(0, |d, v| d.max(*v)),
+                        CompType::Mux => comp.pins_in.iter().fold(0, |d, i| {
+                            let bit = (prev[*i as usize] != 0) as Data;
+                            d | bit << wires[*i as usize].channel
+                        }),
+                        CompType::Demux(value) if (any >> channel) & 1 != 1 => value,
+                        CompType::Demux(_) => 0,
+                        CompType::Fixed(value) => value,
+                        CompType::Input => input[channel as usize],
+                        _ => unreachable!(),
+                    };
+                    next[*pin as usize] |= data & mask;
+                }
+            }
+        }
+        next
+    }
+
+    #[test]
+    fn only_dirty_components_match_full_sweep() {
+        for seed in 1..200 {
+            let input = random_input(seed);
+            let mut sim = Simulator::new(random_schema(seed, false)).unwrap();
+            write_inputs(&mut sim, &input);
+            let schema = random_schema(seed, false);
+            for tick in 1..25 {
+                sim.step(1);
+                let expected = reference(&schema, &input, tick);
+                assert_eq!(read_wires(&sim), expected, "seed {} tick {}", seed, tick);
+            }
+        }
+    }
+
+    #[test]
+    fn new_components_are_evaluated() {
+        let schema = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                gate(Operator::Nor, vec![], vec![0]),
+                comp(CompType::Fixed(7), vec![], vec![1]),
+            ],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        sim.step(1);
+        assert_eq!(sim.read_wire(0), Some(0xffff));
+        assert_eq!(sim.read_wire(1), Some(7));
+        sim.step(3);
+        assert_eq!(read_wires(&sim), vec![0xffff, 7]);
+    }
+
+    #[test]
+    fn data_width() {
+        let parts = |value| {
+            (
+                vec![wire(0), wire(1), wire(0), wire(0), wire(2)],
+                vec![
+                    comp(CompType::Fixed(value), vec![], vec![0]),
+                    comp(CompType::Fixed(100), vec![], vec![1]),
+                    comp(
+                        CompType::Gate(Operator::Add, OverflowMode::Saturating),
+                        vec![0, 1],
+                        vec![2],
+                    ),
+                    gate(Operator::Nor, vec![], vec![3]),
+                    comp(CompType::Input, vec![], vec![4]),
+                ],
+            )
+        };
+        for (bits, value, expected) in [
+            (8, 200, [200, 100, 0xff, 0xff, 0x34]),
+            (
+                64,
+                1 << 40,
+                [1 << 40, 100, (1 << 40) + 100, Data::MAX, 0x1234],
+            ),
+        ] {
+            let (wires, comps) = parts(value);
+            let width = DataWidth(bits);
+            let schema = schema(wires, comps).with_width(width);
+            let path = temp_path("width.blc");
+            schema.save(&path).unwrap();
+            let schema = Schema::load(&path).unwrap();
+            assert_eq!(schema.width(), width);
+            on_every_backend(&schema, |sim| {
+                sim.write_input(2, 0x1234);
+                sim.step(2);
+                assert_eq!(read_wires(sim), expected);
+            });
+        }
+
+        // the width and the constants are checked
+        let (wires, comps) = parts(0x100);
+        let schema = schema(wires, comps);
+        let errors = schema
+            .clone()
+            .with_width(DataWidth(8))
+            .verify()
+            .unwrap_err();
+        assert!(matches!(errors[..], [Error::CompValue(0, 0x100)]));
+        let errors = schema.with_width(DataWidth(65)).verify().unwrap_err();
+        assert!(matches!(errors[..], [Error::DataWidth(65)]));
+    }
+
+    #[test]
+    fn driver_resolution() {
+        let with = |resolution| SchemaWire {
+            resolution: Some(resolution),
+            ..wire(2)
+        };
+        let schema = schema(
+            vec![
+                wire(0),
+                with(Resolution::WiredAnd),
+                with(Resolution::Strict),
+                wire(0),
+            ],
+            vec![
+                comp(CompType::Fixed(0b1100), vec![], vec![0, 1]),
+                comp(CompType::Fixed(0b1010), vec![], vec![0, 1]),
+                comp(CompType::Fixed(5), vec![], vec![2]),
+                comp(CompType::Input, vec![], vec![2]),
+            ],
+        );
+        let warnings = schema.verify().unwrap();
+        assert_eq!(
+            warnings,
+            [
+                Warning::MultipleDrivers(0, 2),
+                Warning::MultipleDrivers(1, 2),
+                Warning::MultipleDrivers(2, 2),
+                Warning::NoDriver(3),
+            ]
+        );
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            assert_eq!(read_wires(sim), [0b1110, 0b1000, 5, 0]);
+            // the conflict is reported again only once the drivers agreed
+            sim.write_input(2, 5);
+            sim.step(2);
+            sim.write_input(2, 7);
+            sim.step(2);
+            assert_eq!(sim.read_wire(2), Some(7));
+            let conflicts = sim.take_conflicts();
+            let reported: Vec<_> = conflicts
+                .iter()
+                .map(|c| (c.wire, c.tick, c.values.clone()))
+                .collect();
+            assert_eq!(reported, [(2, 1, vec![5, 0]), (2, 5, vec![5, 7])]);
+            assert!(sim.take_conflicts().is_empty());
+            conflicts
+        });
+
+        // the resolution of the schematic only applies to the wires without their own
+        let schema = schema.with_resolution(Resolution::WiredAnd);
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            assert_eq!(read_wires(sim), [0b1000, 0b1000, 5, 0]);
+            read_wires(sim)
+        });
+    }
+
+    // data of the second wire seen by the probes on every tick
+    #[derive(Default, Resource)]
+    struct Probed(Vec<Data>, Vec<Data>);
+
+    fn probe_input(wires: Res<SchemaWires>, query: Query<&DataPrev>, mut probed: ResMut<Probed>) {
+        probed.0.push(query.get(wires.0[1]).unwrap().0);
+    }
+
+    fn probe_output(wires: Res<SchemaWires>, query: Query<&DataNext>, mut probed: ResMut<Probed>) {
+        probed.1.push(query.get(wires.0[1]).unwrap().0);
+    }
+
+    #[test]
+    fn probes_follow_the_sets() {
+        let schema = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                comp(CompType::Fixed(5), vec![], vec![0]),
+                gate(Operator::Or, vec![0], vec![1]),
+            ],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        let app = sim.app_mut().unwrap();
+        app.init_resource::<Probed>()
+            .add_systems(CircuitTick, probe_input.in_set(CircuitSet::Input))
+            .add_systems(CircuitTick, probe_output.in_set(CircuitSet::Output));
+        sim.step(3);
+
+        // the input set sees the latched data of the previous tick and the output set
+        // the data evaluated on the tick
+        let app = sim.app_mut().unwrap();
+        let probed = app.world.resource::<Probed>();
+        assert_eq!(probed.0, vec![0, 0, 5]);
+        assert_eq!(probed.1, vec![0, 5, 5]);
+    }
+}
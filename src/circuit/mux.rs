@@ -1,16 +1,17 @@
 use super::*;
 
-/* Multiplexer Entity: CompMux, PinsIn, PinsOut */
+/* Multiplexer Entity: CompMux, PinsIn, PinsOut, DataOut */
 #[derive(Component)]
 pub struct CompMux;
 
 // combine multiple input values as boolean into a single wire
 pub fn sys_tick(
-    comp_query: Query<(&PinsIn, &PinsOut), With<CompMux>>,
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&PinsIn, &mut DataOut), With<CompMux>>,
     prev_query: Query<(&PinChannel, &DataPrev)>,
-    mut next_query: Query<&mut DataNext>,
 ) {
-    for (pins_in, pins_out) in comp_query.iter() {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((pins_in, mut data_out)) = iter.fetch_next() {
         // find the values of input wires
         let mut data: Data = 0;
         for id in pins_in.0.iter() {
@@ -20,10 +21,6 @@ pub fn sys_tick(
         }
 
         // apply the value to all output wires
-        for id in pins_out.0.iter() {
-            if let Ok(mut pin) = next_query.get_mut(*id) {
-                pin.0 |= data;
-            }
-        }
+        drive_all(&mut data_out, data);
     }
 }
@@ -0,0 +1,95 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/* Register Entity (D flip-flop): CompRegister, PinsIn, PinsOut, DataOut */
+// input wires on the clock channel, the value is stored on a rising edge
+pub const REGISTER_CLOCK: Channel = 0;
+// input wires on the enable channel, always enabled if there are none
+pub const REGISTER_ENABLE: Channel = 1;
+// input wires on any other channel carry the data to store
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompRegister {
+    pub value: Data,
+    pub clock: bool,
+}
+
+impl CompRegister {
+    // update the state from the input wires, return the data to output
+    pub fn update(&mut self, inputs: &ByChannel) -> Data {
+        let clock = inputs[REGISTER_CLOCK as usize].unwrap_or(0) != 0;
+        let enable = inputs[REGISTER_ENABLE as usize] != Some(0);
+
+        // store the data on rising edge of the clock
+        if clock && !self.clock && enable {
+            self.value = inputs
+                .iter()
+                .enumerate()
+                .filter(|(channel, _)| {
+                    *channel != REGISTER_CLOCK as usize && *channel != REGISTER_ENABLE as usize
+                })
+                .fold(0, |data, (_, value)| data | value.unwrap_or(0));
+        }
+        self.clock = clock;
+        self.value
+    }
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut CompRegister, &PinsIn, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut register, pins_in, mut data_out)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        let data = register.update(&inputs);
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+
+    #[test]
+    fn stores_on_rising_edge() {
+        let mut register = CompRegister::default();
+        let mut inputs: ByChannel = [None; NB_CHANNELS];
+        inputs[2] = Some(7);
+        inputs[5] = Some(8);
+        assert_eq!(register.update(&inputs), 0);
+        inputs[REGISTER_CLOCK as usize] = Some(1);
+        assert_eq!(register.update(&inputs), 15);
+        // the clock stays high, the new data is ignored
+        inputs[2] = Some(1);
+        assert_eq!(register.update(&inputs), 15);
+        // disabled on the next rising edge
+        inputs[REGISTER_CLOCK as usize] = Some(0);
+        inputs[REGISTER_ENABLE as usize] = Some(0);
+        register.update(&inputs);
+        inputs[REGISTER_CLOCK as usize] = Some(1);
+        assert_eq!(register.update(&inputs), 15);
+    }
+
+    #[test]
+    fn clocked_by_input() {
+        let schema = schema(
+            vec![wire(REGISTER_CLOCK), wire(2), wire(5)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                comp(CompType::Fixed(7), vec![], vec![1]),
+                comp(CompType::Register, vec![0, 1], vec![2]),
+            ],
+        );
+        on_every_backend(&schema, |sim| {
+            sim.step(5);
+            assert_eq!(sim.read_wire(2), Some(0));
+            sim.write_input(REGISTER_CLOCK, 1);
+            sim.step(3);
+            assert_eq!(sim.read_wire(2), Some(7));
+        });
+    }
+}
@@ -0,0 +1,254 @@
+use super::*;
+use crate::trace::Breakpoints;
+use bevy::ecs::schedule::ScheduleLabel;
+
+// schedule running a single tick of the circuit
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CircuitTick;
+
+// steps of a tick, always executed in this order:
+// - Latch: the data of the previous tick becomes visible on every wire (`DataPrev`)
+//   and components reading from wires that changed are flagged for evaluation
+// - Input: devices outside of the circuit drive their output pins (`DataOut`)
+// - Evaluate: components compute their output pins from `DataPrev` of their inputs,
+//   once the set is done the drivers of each wire are combined into `DataNext`
+// - Output: `DataNext` holds the final value of the tick, ready to be displayed or probed
+// the sets only exist in the `CircuitTick` schedule which runs once per tick, a probe or
+// a custom component must be added there, e.g. `add_systems(CircuitTick, probe.in_set(
+// CircuitSet::Output))`, a system in `Update` only runs once per frame whatever the
+// number of ticks and sees the data of the last tick of the previous frame
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CircuitSet {
+    Latch,
+    Input,
+    Evaluate,
+    Output,
+}
+
+// whether the circuit runs on its own or waits for steps
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SimState {
+    #[default]
+    Running,
+    Paused,
+}
+
+// speed at which the circuit runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TickRate {
+    // a fixed amount of ticks per second, independent of the frame rate
+    PerSecond(f64),
+    // a fixed amount of ticks on every frame, for turbo mode
+    PerFrame(u32),
+}
+
+// number of ticks executed since the circuit was built
+#[derive(Default, Resource)]
+pub struct TickCount(pub u64);
+
+/* Tick Scheduler: decide how many ticks to run on each frame */
+#[derive(Resource)]
+pub struct TickScheduler {
+    pub rate: TickRate,
+    // upper bound of ticks per frame whatever the rate or the steps,
+    // avoid freezing the app when a frame takes too long
+    pub max_per_frame: u32,
+    // ticks requested with `step`, the ones above the bound wait for the next frames
+    steps: u32,
+    // fraction of tick accumulated from previous frames
+    elapsed: f64,
+}
+
+impl Default for TickScheduler {
+    fn default() -> Self {
+        Self {
+            rate: TickRate::PerFrame(1),
+            max_per_frame: 1000,
+            steps: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl TickScheduler {
+    // run the given amount of ticks on the next frame, even if paused
+    pub fn step(&mut self, n: u32) {
+        self.steps = self.steps.saturating_add(n);
+    }
+
+    // number of ticks to run for a frame of the given duration
+    fn advance(&mut self, delta: f64, running: bool) -> u32 {
+        let rated = if running {
+            match self.rate {
+                TickRate::PerFrame(n) => n,
+                TickRate::PerSecond(rate) => {
+                    self.elapsed += delta * rate.max(0.0);
+                    let n = self.elapsed.floor();
+                    self.elapsed -= n;
+                    n as u32
+                }
+            }
+        } else {
+            // do not catch up on the time spent paused
+            self.elapsed = 0.0;
+            0
+        };
+        // the steps come first, the ticks of the rate above the bound are dropped
+        // so that the circuit slows down instead of falling behind
+        let steps = self.steps.min(self.max_per_frame);
+        self.steps -= steps;
+        steps + rated.min(self.max_per_frame - steps)
+    }
+}
+
+// pause or resume the circuit when F5 is pressed, run a single tick when F6 is pressed
+pub fn sys_keys(
+    keys: Option<Res<Input<KeyCode>>>,
+    state: Res<State<SimState>>,
+    mut next_state: ResMut<NextState<SimState>>,
+    mut scheduler: ResMut<TickScheduler>,
+) {
+    let Some(keys) = keys else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::F5) {
+        next_state.set(match state.get() {
+            SimState::Running => SimState::Paused,
+            SimState::Paused => SimState::Running,
+        });
+    }
+    if keys.just_pressed(KeyCode::F6) {
+        scheduler.step(1);
+    }
+}
+
+// run as many ticks as the scheduler requires for this frame,
+// the remaining ticks are dropped when a breakpoint fires
+pub fn sys_run(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds_f64();
+    let running = *world.resource::<State<SimState>>().get() == SimState::Running;
+    let count = world
+        .resource_mut::<TickScheduler>()
+        .advance(delta, running);
+    for _ in 0..count {
+        world.run_schedule(CircuitTick);
+        if world
+            .get_resource::<Breakpoints>()
+            .is_some_and(|breakpoints| breakpoints.is_hit())
+        {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::simulator::Simulator;
+
+    #[test]
+    fn advance_per_second() {
+        let mut scheduler = TickScheduler {
+            rate: TickRate::PerSecond(10.0),
+            max_per_frame: 4,
+            ..Default::default()
+        };
+        assert_eq!(scheduler.advance(0.25, true), 2);
+        // the remaining half tick is carried over
+        assert_eq!(scheduler.advance(0.25, true), 3);
+        // capped when a frame takes too long
+        assert_eq!(scheduler.advance(10.0, true), 4);
+        assert_eq!(scheduler.advance(0.25, false), 0);
+        scheduler.step(3);
+        assert_eq!(scheduler.advance(0.0, false), 3);
+        assert_eq!(scheduler.advance(0.0, false), 0);
+    }
+
+    #[test]
+    fn advance_bounded() {
+        let mut scheduler = TickScheduler {
+            rate: TickRate::PerFrame(u32::MAX),
+            max_per_frame: 4,
+            ..Default::default()
+        };
+        assert_eq!(scheduler.advance(0.0, true), 4);
+        scheduler.step(u32::MAX);
+        scheduler.step(6);
+        assert_eq!(scheduler.advance(0.0, true), 4);
+        // the steps above the bound are kept for the next frames
+        scheduler.rate = TickRate::PerFrame(1);
+        scheduler.max_per_frame = u32::MAX - 6;
+        assert_eq!(scheduler.advance(0.0, true), u32::MAX - 6);
+        assert_eq!(scheduler.advance(0.0, false), 2);
+        assert_eq!(scheduler.advance(0.0, true), 1);
+    }
+
+    #[test]
+    fn pause_and_step() {
+        let mut sim = Simulator::new(random_schema(3, false)).unwrap();
+        let app = sim.app_mut().unwrap();
+        app.world.resource_mut::<TickScheduler>().rate = TickRate::PerFrame(3);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, 3);
+
+        // the state changes at the start of the next frame
+        app.world
+            .resource_mut::<NextState<SimState>>()
+            .set(SimState::Paused);
+        app.update();
+        let ticks = app.world.resource::<TickCount>().0;
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks);
+        app.world.resource_mut::<TickScheduler>().step(1);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks + 1);
+
+        sim.step(5);
+        assert_eq!(sim.ticks(), ticks + 6);
+    }
+
+    #[test]
+    fn pause_and_step_with_keys() {
+        use bevy::input::{keyboard::KeyboardInput, ButtonState};
+
+        // press the key and release it on the same frame
+        let press = |app: &mut App, key_code| {
+            for state in [ButtonState::Pressed, ButtonState::Released] {
+                app.world.send_event(KeyboardInput {
+                    scan_code: 0,
+                    key_code: Some(key_code),
+                    state,
+                    window: Entity::PLACEHOLDER,
+                });
+            }
+        };
+        let mut sim = Simulator::new(random_schema(3, false)).unwrap();
+        let app = sim.app_mut().unwrap();
+        press(app, KeyCode::F5);
+        app.update();
+        app.update();
+        assert_eq!(
+            *app.world.resource::<State<SimState>>().get(),
+            SimState::Paused
+        );
+        let ticks = app.world.resource::<TickCount>().0;
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks);
+
+        // a single tick on the frame the key is pressed
+        press(app, KeyCode::F6);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks + 1);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks + 1);
+
+        press(app, KeyCode::F5);
+        app.update();
+        app.update();
+        assert_eq!(
+            *app.world.resource::<State<SimState>>().get(),
+            SimState::Running
+        );
+    }
+}
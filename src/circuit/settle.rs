@@ -0,0 +1,254 @@
+use super::*;
+use crate::schematic::SchemaWires;
+use bevy::ecs::schedule::ScheduleLabel;
+use serde::{Deserialize, Serialize};
+
+// when the data evaluated by the components reaches the wires
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource, Deserialize, Serialize)]
+pub enum TimingMode {
+    // every component takes a tick to drive its outputs, a circuit as deep as its number
+    // of components takes as many ticks to give its result
+    #[default]
+    UnitDelay,
+    // the combinational components are evaluated again within the tick until the wires
+    // settle, only the sequential components move to the next tick on their own, the
+    // components with a delay of their own keep it
+    Settle,
+}
+
+// combinational loop which did not settle within a tick,
+// with the index of the wires still changing once the tick is done
+#[derive(Clone, Debug, Event, PartialEq, Eq)]
+pub struct Oscillation {
+    pub tick: u64,
+    pub wires: Vec<Index>,
+}
+
+// schedule running a single pass of the combinational components, see `sys_settle`
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CircuitSettle;
+
+// passes run on top of one per settling component before the circuit is said to oscillate
+pub const EXTRA_PASSES: usize = 2;
+
+/* Settling: passes over the combinational components during a tick */
+#[derive(Resource)]
+pub struct Settling {
+    // passes after which the wires are said to oscillate
+    limit: usize,
+    // wires which data changed on the last pass
+    changed: Vec<Index>,
+}
+
+impl Settling {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl Default for Settling {
+    fn default() -> Self {
+        Self::new(EXTRA_PASSES)
+    }
+}
+
+// move the data driven by the last pass to the wires, keep track of the ones which changed
+pub fn sys_tock(
+    mut settling: ResMut<Settling>,
+    schema_wires: Res<SchemaWires>,
+    mut query: Query<(&mut DataPrev, &DataNext)>,
+) {
+    settling.changed.clear();
+    for (i, wire) in schema_wires.0.iter().enumerate() {
+        if let Ok((mut wire_prev, wire_next)) = query.get_mut(*wire) {
+            if wire_prev.set_if_neq(DataPrev(wire_next.0)) {
+                settling.changed.push(i as Index);
+            }
+        }
+    }
+}
+
+// only the components without a delay of their own are evaluated again while settling,
+// the delayed ones wait for the next tick like the sequential ones
+pub fn sys_keep(mut dirty: ResMut<DirtyComps>, delayed_query: Query<(), With<Pipeline>>) {
+    dirty.0.retain(|id| !delayed_query.contains(*id));
+}
+
+// run passes over the combinational components until the wires stop changing,
+// the wires changing on the second half of the passes are reported when they never do,
+// the data of the wires at the start of the tick is kept as the one of the previous tick
+pub fn sys_settle(world: &mut World) {
+    let mut query = world.query::<(Entity, &DataPrev)>();
+    let start: Vec<(Entity, Data)> = query.iter(world).map(|(e, d)| (e, d.0)).collect();
+
+    let limit = world.resource::<Settling>().limit;
+    let mut wires = Vec::<Index>::new();
+    let mut settled = false;
+    for pass in 0..limit {
+        world.run_schedule(CircuitSettle);
+        let changed = &world.resource::<Settling>().changed;
+        if changed.is_empty() {
+            settled = true;
+            break;
+        }
+        if pass >= limit / 2 {
+            wires.extend_from_slice(changed);
+        }
+    }
+
+    for (entity, data) in start {
+        if let Some(mut wire_prev) = world.get_mut::<DataPrev>(entity) {
+            wire_prev.set_if_neq(DataPrev(data));
+        }
+    }
+    if !settled {
+        wires.sort_unstable();
+        wires.dedup();
+        let tick = world.resource::<TickCount>().0;
+        world
+            .resource_mut::<Events<Oscillation>>()
+            .send(Oscillation { tick, wires });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompType, Schema, SchemaComp};
+
+    // full adder of the bits on the channels 0, 1 and 2 of the input,
+    // with the sum on the wire 3 and the carry on the wire 4
+    fn full_adder() -> Schema {
+        schema(
+            (0..8).map(|i| wire(i.min(3))).collect(),
+            vec![
+                comp(CompType::Input, vec![], vec![0, 1, 2]),
+                gate(Operator::Xor, vec![0, 1], vec![5]),
+                gate(Operator::Xor, vec![5, 2], vec![3]),
+                gate(Operator::And, vec![0, 1], vec![6]),
+                gate(Operator::And, vec![5, 2], vec![7]),
+                gate(Operator::Or, vec![6, 7], vec![4]),
+            ],
+        )
+    }
+
+    #[test]
+    fn adder_settles() {
+        let schema = full_adder().with_timing(TimingMode::Settle);
+        assert_eq!(schema.settle_limit(), 7);
+        // the plugin settles an empty circuit like the schematic does
+        assert_eq!(Settling::default().limit, Schema::default().settle_limit());
+        on_every_backend(&schema, |sim| {
+            let sums: Vec<_> = (0..8)
+                .map(|bits: Data| {
+                    for channel in 0..3 {
+                        sim.write_input(channel, (bits >> channel) & 1);
+                    }
+                    sim.step(1);
+                    (sim.read_wire(3).unwrap(), sim.read_wire(4).unwrap())
+                })
+                .collect();
+            let expected: Vec<_> = (0..8)
+                .map(|bits: Data| {
+                    let ones = bits.count_ones() as Data;
+                    (ones & 1, ones >> 1)
+                })
+                .collect();
+            assert_eq!(sums, expected);
+            assert!(sim.take_oscillations().is_empty());
+            sums
+        });
+
+        // the unit delay needs a tick per gate on the way
+        let mut sim = crate::simulator::Simulator::new(full_adder()).unwrap();
+        (0..3).for_each(|channel| sim.write_input(channel, 1));
+        sim.step(1);
+        assert_eq!((sim.read_wire(3), sim.read_wire(4)), (Some(0), Some(0)));
+        sim.step(3);
+        assert_eq!((sim.read_wire(3), sim.read_wire(4)), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn reports_oscillations() {
+        let schema = schema(
+            vec![wire(0), wire(0), wire(0), wire(0)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                // an inverter looping on itself never settles
+                gate(Operator::Nor, vec![0, 1], vec![1]),
+                // a loop holding its data settles
+                gate(Operator::Or, vec![0, 2], vec![2]),
+                comp(CompType::Fixed(3), vec![], vec![3]),
+            ],
+        )
+        .with_timing(TimingMode::Settle);
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            let oscillations = sim.take_oscillations();
+            let wires: Vec<_> = oscillations.iter().map(|o| o.wires.clone()).collect();
+            assert_eq!(wires, [vec![1], vec![1]]);
+            assert_eq!(oscillations[1].tick, 2);
+
+            // the inverter stops once its other input holds it
+            let on = sim.width().mask();
+            sim.write_input(0, on);
+            sim.step(2);
+            assert_eq!(sim.read_wire(1), Some(0));
+            assert_eq!(sim.read_wire(2), Some(on));
+            assert_eq!(sim.read_wire(3), Some(3));
+            assert!(sim.take_oscillations().is_empty());
+            read_wires(sim)
+        });
+    }
+
+    #[test]
+    fn delayed_gates_wait() {
+        let delayed = SchemaComp {
+            delay: Some(3),
+            ..gate(Operator::Or, vec![0], vec![1])
+        };
+        let schema = schema(
+            vec![wire(0), wire(0), wire(0)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                delayed,
+                gate(Operator::Or, vec![1], vec![2]),
+            ],
+        )
+        .with_timing(TimingMode::Settle);
+        on_every_backend(&schema, |sim| {
+            sim.write_input(0, 9);
+            let wires: Vec<_> = (0..6)
+                .map(|_| {
+                    sim.step(1);
+                    (sim.read_wire(1).unwrap(), sim.read_wire(2).unwrap())
+                })
+                .collect();
+            // the delay counts from the tick the input reaches the gate
+            assert_eq!(wires, [(0, 0), (0, 0), (0, 0), (9, 9), (9, 9), (9, 9)]);
+            wires
+        });
+    }
+
+    #[test]
+    fn matches_across_backends() {
+        for seed in 1..100 {
+            let input = random_input(seed);
+            let schema = random_schema(seed, seed % 2 == 0).with_timing(TimingMode::Settle);
+            on_every_backend(&schema, |sim| {
+                write_inputs(sim, &input);
+                let wires: Vec<_> = (1..10)
+                    .map(|_| {
+                        sim.step(1);
+                        read_wires(sim)
+                    })
+                    .collect();
+                (wires, sim.take_oscillations())
+            });
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/* T Flip-Flop Entity: CompToggle, PinsIn, PinsOut, DataOut */
+// input wires on the clock channel, the state is toggled on a rising edge
+pub const TOGGLE_CLOCK: Channel = 0;
+// input wires on the toggle channel, always toggles if there are none
+pub const TOGGLE_ENABLE: Channel = 1;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompToggle {
+    pub state: bool,
+    pub clock: bool,
+}
+
+impl CompToggle {
+    // update the state from the input wires, return the data to output
+    pub fn update(&mut self, inputs: &ByChannel) -> Data {
+        let clock = inputs[TOGGLE_CLOCK as usize].unwrap_or(0) != 0;
+        let enable = inputs[TOGGLE_ENABLE as usize] != Some(0);
+
+        // flip the state on rising edge of the clock
+        if clock && !self.clock && enable {
+            self.state = !self.state;
+        }
+        self.clock = clock;
+        data_from_bool(self.state)
+    }
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut CompToggle, &PinsIn, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut toggle, pins_in, mut data_out)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        let data = toggle.update(&inputs);
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_on_rising_edge() {
+        let mut toggle = CompToggle::default();
+        let mut inputs: ByChannel = [None; NB_CHANNELS];
+        let mut states = Vec::new();
+        for clock in [1, 1, 0, 1, 0, 0, 1] {
+            inputs[TOGGLE_CLOCK as usize] = Some(clock);
+            states.push(toggle.update(&inputs) != 0);
+        }
+        assert_eq!(states, [true, true, true, false, false, false, true]);
+        inputs[TOGGLE_CLOCK as usize] = Some(0);
+        inputs[TOGGLE_ENABLE as usize] = Some(0);
+        toggle.update(&inputs);
+        inputs[TOGGLE_CLOCK as usize] = Some(1);
+        assert_eq!(toggle.update(&inputs), Data::MAX);
+    }
+}
@@ -0,0 +1,141 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/* Tri-State Buffer Entity: CompTriState, PinsIn, PinsOut, DataOut */
+// input wires on the enable channel, always enabled if there are none
+pub const TRISTATE_ENABLE: Channel = 0;
+
+// input wires on any other channel carry the data to pass through,
+// a disabled buffer lets go of its output wires and is not one of their drivers
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CompTriState {
+    pub enabled: bool,
+}
+
+impl CompTriState {
+    // update the state from the input wires, return the data to output
+    pub fn update(&mut self, inputs: &ByChannel) -> Data {
+        self.enabled = inputs[TRISTATE_ENABLE as usize] != Some(0);
+        if !self.enabled {
+            return 0;
+        }
+        inputs
+            .iter()
+            .enumerate()
+            .filter(|(channel, _)| *channel != TRISTATE_ENABLE as usize)
+            .fold(0, |data, (_, value)| data | value.unwrap_or(0))
+    }
+}
+
+pub fn sys_tick(
+    dirty: Res<DirtyComps>,
+    mut comp_query: Query<(&mut CompTriState, &PinsIn, &mut DataOut)>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    let mut iter = comp_query.iter_many_mut(&dirty.0);
+    while let Some((mut tristate, pins_in, mut data_out)) = iter.fetch_next() {
+        let inputs = read_by_channel(pins_in, &prev_query);
+        let mut next = *tristate;
+        let data = next.update(&inputs);
+        // only flag the buffers which let go of their wires or took them back
+        tristate.set_if_neq(next);
+        drive_all(&mut data_out, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::{CompKind, CompType, Error, SchemaWire};
+
+    #[test]
+    fn passes_data_when_enabled() {
+        let mut tristate = CompTriState::default();
+        let mut inputs: ByChannel = [None; NB_CHANNELS];
+        inputs[2] = Some(6);
+        inputs[5] = Some(1);
+        assert_eq!(tristate.update(&inputs), 7);
+        assert!(tristate.enabled);
+        inputs[TRISTATE_ENABLE as usize] = Some(0);
+        assert_eq!(tristate.update(&inputs), 0);
+        assert!(!tristate.enabled);
+        inputs[TRISTATE_ENABLE as usize] = Some(4);
+        assert_eq!(tristate.update(&inputs), 7);
+    }
+
+    #[test]
+    fn shared_bus() {
+        let with = |resolution| SchemaWire {
+            resolution: Some(resolution),
+            ..wire(1)
+        };
+        // two buffers enabled by the inputs on channels 0 and 2, driving a strict bus
+        // and a wired-and one
+        let schema = schema(
+            vec![
+                wire(TRISTATE_ENABLE),
+                wire(TRISTATE_ENABLE),
+                wire(1),
+                wire(1),
+                with(Resolution::Strict),
+                with(Resolution::WiredAnd),
+                wire(2),
+            ],
+            vec![
+                comp(CompType::Input, vec![], vec![0, 6]),
+                gate(Operator::Or, vec![6], vec![1]),
+                comp(CompType::Fixed(5), vec![], vec![2]),
+                comp(CompType::Fixed(3), vec![], vec![3]),
+                comp(CompType::TriState, vec![0, 2], vec![4, 5]),
+                comp(CompType::TriState, vec![1, 3], vec![4, 5]),
+            ],
+        );
+        // buffers sharing a wire are not reported as multiple drivers
+        assert!(schema.verify().unwrap().is_empty());
+
+        on_every_backend(&schema, |sim| {
+            // both disabled, the wires have no driver
+            sim.step(3);
+            assert_eq!(sim.read_wire(4), Some(0));
+            assert_eq!(sim.read_wire(5), Some(0));
+
+            // a single driver, the wired-and wire is not pulled down by the other one
+            sim.write_input(0, 1);
+            sim.step(3);
+            assert_eq!(sim.read_wire(4), Some(5));
+            assert_eq!(sim.read_wire(5), Some(5));
+            assert!(sim.take_conflicts().is_empty());
+
+            // both enabled, the strict bus reports the conflict
+            sim.write_input(2, 1);
+            sim.step(3);
+            assert_eq!(sim.read_wire(5), Some(1));
+            let conflicts = sim.take_conflicts();
+            let reported: Vec<_> = conflicts
+                .iter()
+                .map(|c| (c.wire, c.values.clone()))
+                .collect();
+            assert_eq!(reported, [(4, vec![5, 3])]);
+
+            // released again, the conflict is over
+            sim.write_input(0, 0);
+            sim.step(3);
+            assert_eq!(sim.read_wire(4), Some(3));
+            assert!(sim.take_conflicts().is_empty());
+            conflicts
+        });
+    }
+
+    #[test]
+    fn not_delayed() {
+        // a buffer lets go of its wires on the tick it is disabled
+        let schema = schema(
+            vec![wire(1)],
+            vec![comp(CompType::TriState, vec![], vec![0])],
+        )
+        .with_delay(CompKind::TriState, 2);
+        let errors = schema.verify().unwrap_err();
+        assert!(matches!(errors[..], [Error::CompDelay(0, 2)]));
+    }
+}
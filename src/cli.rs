@@ -1,47 +1,175 @@
+use bevy_logic_circuit::circuit::{DataWidth, KeyMap, TickRate};
+use bevy_logic_circuit::importer::load_xraw_file;
+use bevy_logic_circuit::schematic::{BusBackend, Schema};
+use bevy_logic_circuit::simulator::Backend;
+use bevy_logic_circuit::trace::{Breakpoints, Condition};
 use clap::Parser;
+use std::error;
 use std::path::PathBuf;
-use crate::importer::*;
 
 /// Build voxel logic circuits to execute
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about=None)]
-struct Cli {
-
-    /// Input file to load to build a logic circuit
+pub struct Cli {
+    /// Input file to load to build a logic circuit, an empty circuit if none,
+    /// .xraw voxel models are imported
     #[clap(short, long, parse(from_os_str))]
-    input_file: PathBuf,
+    pub input_file: Option<PathBuf>,
+
+    /// Bits carried by the wires of an imported voxel model
+    #[clap(long, default_value = "16")]
+    pub width: u32,
+
+    /// Ticks to run per second, independent of the frame rate
+    #[clap(long, conflicts_with = "turbo")]
+    pub rate: Option<f64>,
+
+    /// Ticks to run on every frame
+    #[clap(long)]
+    pub turbo: Option<u32>,
+
+    /// Keys driving the input device: directions, ascii or a RON file mapping
+    /// each key to a channel and a bit
+    #[clap(long, default_value = "directions")]
+    pub keymap: String,
+
+    /// Backend of the bus on a port, its rank among the buses: PORT=stdio,
+    /// PORT=files:INPUT,OUTPUT or PORT=socket:PATH
+    #[clap(long = "bus", parse(try_from_str = parse_bus))]
+    pub buses: Vec<(usize, BusBackend)>,
+
+    /// Check the circuit, print its errors and warnings, then exit
+    #[clap(long)]
+    pub verify: bool,
+
+    /// Run the given amount of ticks without window, then print every wire and display
+    #[clap(long)]
+    pub headless: Option<u64>,
+
+    /// Implementation running the circuit without window: ecs, the default, or netlist
+    #[clap(long, requires = "headless", parse(try_from_str = parse_backend))]
+    pub backend: Option<Backend>,
+
+    /// Pause the circuit when a condition fires: wire:INDEX=DATA when the wire holds
+    /// the data, wire:INDEX when it changes, comp:INDEX when an output is not null
+    #[clap(long = "break", parse(try_from_str = parse_condition))]
+    pub breakpoints: Vec<Condition>,
+
+    /// File to write the data of every wire on each tick to, as a Value Change Dump,
+    /// when the window is closed or the headless run ends
+    #[clap(long, parse(from_os_str))]
+    pub vcd: Option<PathBuf>,
+
+    /// Ticks kept in the Value Change Dump, the last ones
+    #[clap(long, requires = "vcd", default_value = "65536")]
+    pub vcd_ticks: usize,
+
+    /// Warn about the wires which data repeats with a period of at most the given ticks,
+    /// like loops toggling forever
+    #[clap(long, value_name = "MAX_PERIOD")]
+    pub detect_cycles: Option<usize>,
 
+    /// Snapshot file to resume the circuit from
+    #[clap(long, parse(from_os_str))]
+    pub load_state: Option<PathBuf>,
+
+    /// Snapshot file to save the circuit to when the window is closed
+    /// or the headless run ends
+    #[clap(long, parse(from_os_str))]
+    pub save_state: Option<PathBuf>,
+
+    /// Ticks kept to go back in time with F7 in the window
+    #[clap(long, conflicts_with = "headless")]
+    pub history: Option<usize>,
+
+    /// Directory to write the memories to when the window is closed
+    #[clap(long, parse(from_os_str))]
+    pub dump_memories: Option<PathBuf>,
 }
 
+impl Cli {
+    // speed at which the circuit runs, the default one of the scheduler if none
+    pub fn tick_rate(&self) -> Option<TickRate> {
+        match (self.rate, self.turbo) {
+            (Some(rate), _) => Some(TickRate::PerSecond(rate)),
+            (None, Some(n)) => Some(TickRate::PerFrame(n)),
+            (None, None) => None,
+        }
+    }
 
-fn parse_args() {
-    let args = Cli::parse();
+    // schematic to build, imported if the file is a voxel model
+    pub fn schema(&self) -> Result<Schema, Box<dyn error::Error>> {
+        let Some(path) = &self.input_file else {
+            return Ok(Schema::default());
+        };
+        if path.extension().is_some_and(|ext| ext == "xraw") {
+            let width = DataWidth(self.width);
+            if !width.is_valid() {
+                return Err(format!("invalid width {}", self.width).into());
+            }
+            return Ok(load_xraw_file(path, width)?);
+        }
+        Schema::load(path)
+    }
 
-    let file_path = args.input_file;
+    // conditions given on the command line, none if there are no conditions
+    pub fn breakpoints(&self) -> Option<Breakpoints> {
+        let mut breakpoints = Breakpoints::default();
+        for condition in self.breakpoints.iter() {
+            breakpoints.add(*condition);
+        }
+        (!self.breakpoints.is_empty()).then_some(breakpoints)
+    }
 
-    // test the file extension
-    let schema = match file_path.extension() {
-        Some("blc")  => {
-            Schema::load(file_path)
-        },
-        Some("xraw") => {
-            let matrix = xraw::load_file(file_path);
-            
+    // keys driving the input device, one of the presets or loaded from a file,
+    // checked against the width of the wires
+    pub fn key_map(&self, width: DataWidth) -> Result<KeyMap, Box<dyn error::Error>> {
+        let map = match self.keymap.as_str() {
+            "directions" => KeyMap::directions(),
+            "ascii" => KeyMap::ascii(),
+            path => return KeyMap::load(path, width),
+        };
+        map.check(width)?;
+        Ok(map)
+    }
+}
+
+// port and backend of a bus given on the command line
+fn parse_bus(arg: &str) -> Result<(usize, BusBackend), String> {
+    let (port, backend) = arg.split_once('=').ok_or("expected PORT=BACKEND")?;
+    let port = port.parse().map_err(|_| format!("invalid port {}", port))?;
+    let backend = match backend.split_once(':') {
+        None if backend == "stdio" => BusBackend::Stdio,
+        Some(("files", files)) => match files.split_once(',') {
+            Some((input, output)) => BusBackend::Files(input.into(), output.into()),
+            None => return Err("expected files:INPUT,OUTPUT".into()),
         },
-        Some("vox")  => Err(ErrorFile::Unknown),
-        None         => Err(ErrorFile::Unknown),
+        Some(("socket", path)) => BusBackend::Socket(path.into()),
+        _ => return Err(format!("unknown backend {}", backend)),
     };
+    Ok((port, backend))
+}
 
-    let schema = match result {
-        Ok(s) => {
-            if let Err(errors) = s.verify() {
-                // TODO: do something with errors
-                return;
-            }
-            s
-        },
-        Err(_) => {
+// implementation running the circuit without window
+fn parse_backend(arg: &str) -> Result<Backend, String> {
+    match arg {
+        "ecs" => Ok(Backend::Ecs),
+        "netlist" => Ok(Backend::Netlist),
+        _ => Err(format!("unknown backend {}", arg)),
+    }
+}
 
+// condition of a breakpoint given on the command line
+fn parse_condition(arg: &str) -> Result<Condition, String> {
+    fn number<T: std::str::FromStr>(n: &str) -> Result<T, String> {
+        n.parse().map_err(|_| format!("invalid number {}", n))
+    }
+    match arg.split_once(':') {
+        Some(("wire", wire)) => match wire.split_once('=') {
+            Some((index, data)) => Ok(Condition::WireEquals(number(index)?, number(data)?)),
+            None => Ok(Condition::WireChanged(number(wire)?)),
         },
-    };
-}
\ No newline at end of file
+        Some(("comp", comp)) => Ok(Condition::CompOutput(number(comp)?)),
+        _ => Err("expected wire:INDEX=DATA, wire:INDEX or comp:INDEX".into()),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cli::Cli;
+use bevy_logic_circuit::schematic::{Error, Schema};
+use bevy_logic_circuit::simulator::Simulator;
+
+// run the circuit without window for the given amount of ticks or until a breakpoint,
+// then print the data of every wire and what every display shows
+pub fn run(schema: Schema, cli: &Cli, ticks: u64) -> Result<(), Vec<Error>> {
+    let ports = schema.prepare()?.open_buses()?;
+    let nb_comps = schema.comps().len() as u32;
+    let mut sim = Simulator::with_backend(schema, cli.backend.unwrap_or_default())?;
+    for (port, backend) in ports.into_iter().enumerate() {
+        sim.set_bus_backend(port, backend);
+    }
+    for condition in cli.breakpoints.iter() {
+        sim.add_breakpoint(*condition);
+    }
+
+    if let Some(path) = &cli.load_state {
+        if let Err(e) = sim.load_state(path) {
+            eprintln!("Cannot resume from {}: {}", path.display(), e);
+        }
+    }
+    if cli.vcd.is_some() {
+        sim.start_trace(None, cli.vcd_ticks);
+    }
+    if let Some(max_period) = cli.detect_cycles {
+        sim.start_cycle_detector(None, max_period);
+    }
+
+    // stop on the first tick a breakpoint fires
+    sim.step(ticks);
+    for hit in sim.take_hits() {
+        println!(
+            "breakpoint {} at tick {}, {:?}",
+            hit.id, hit.tick, hit.condition
+        );
+    }
+    for conflict in sim.take_conflicts() {
+        eprintln!(
+            "Driver conflict at tick {} on wire {}, values={:?}",
+            conflict.tick, conflict.wire, conflict.values
+        );
+    }
+    for oscillation in sim.take_oscillations() {
+        eprintln!(
+            "Oscillation at tick {}, wires={:?}",
+            oscillation.tick, oscillation.wires
+        );
+    }
+    for cycle in sim.take_cycles() {
+        eprintln!(
+            "Wire {} cycles every {} ticks at tick {}",
+            cycle.wire, cycle.period, cycle.tick
+        );
+    }
+
+    if let (Some(path), Some(trace)) = (&cli.vcd, sim.trace()) {
+        if let Err(e) = trace.save_vcd(path) {
+            eprintln!("Cannot write {}: {}", path.display(), e);
+        }
+    }
+
+    if let Some(path) = &cli.save_state {
+        if let Err(e) = sim.save_state(path) {
+            eprintln!("Cannot save {}: {}", path.display(), e);
+        }
+    }
+
+    println!("ticks {}", sim.ticks());
+    for wire in 0..sim.wire_count() as u32 {
+        let channel = sim.wire_channel(wire).unwrap_or_default();
+        let data = sim.read_wire(wire).unwrap_or_default();
+        println!("wire {} ch{} = {}", wire, channel, data);
+    }
+    for comp in 0..nb_comps {
+        if let Some(display) = sim.display(comp) {
+            println!("comp {} {}", comp, display);
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,38 @@
-use std::{str::from_utf8, io};
 use crate::math::Vec3i;
-
+use std::{error, fmt, io};
 
 // indicate the type of error encountered while trying to load a file
+#[derive(Debug)]
 pub enum ImportError {
     File(io::Error),
     Header(usize, usize),
+    Magic(String),
     Content,
     Matrix,
     Schema,
 }
 
+impl error::Error for ImportError {}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::File(e) => write!(f, "File Error, {}", e),
+            Self::Header(expected, read) => {
+                write!(f, "Header Error, expected={} read={}", expected, read)
+            }
+            Self::Magic(magic) => write!(f, "Magic Error, magic={}", magic),
+            Self::Content => write!(f, "Content Error"),
+            Self::Matrix => write!(f, "Matrix Error, unsupported bits per channel"),
+            Self::Schema => write!(f, "Schema Error, only indexed voxels describe components"),
+        }
+    }
+}
 
 // read a string
 #[inline]
 pub fn read_string(buffer: &[u8], index: usize, length: usize) -> String {
-    from_utf8(&buffer[index..(index + length)]).unwrap().to_string()
+    String::from_utf8_lossy(&buffer[index..(index + length)]).to_string()
 }
 
 // read a number
@@ -36,14 +53,13 @@ pub fn read_u64(buffer: &[u8], index: usize) -> u64 {
     u64::from_le_bytes((&buffer[index..(index + 8)]).try_into().unwrap())
 }
 
-
 // read a 3D vector
 #[inline]
 pub fn read_vec3i_from_u8s(buffer: &[u8], index: usize) -> Vec3i {
     Vec3i::new(
-        buffer[index    ] as usize,
+        buffer[index] as usize,
         buffer[index + 1] as usize,
-        buffer[index + 2] as usize
+        buffer[index + 2] as usize,
     )
 }
 
@@ -51,9 +67,9 @@ pub fn read_vec3i_from_u8s(buffer: &[u8], index: usize) -> Vec3i {
 #[inline]
 pub fn read_vec3i_from_u16s(buffer: &[u8], index: usize) -> Vec3i {
     Vec3i::new(
-        read_u16(&buffer, index    ) as usize,
-        read_u16(&buffer, index + 2) as usize,
-        read_u16(&buffer, index + 4) as usize
+        read_u16(buffer, index) as usize,
+        read_u16(buffer, index + 2) as usize,
+        read_u16(buffer, index + 4) as usize,
     )
 }
 
@@ -61,9 +77,9 @@ pub fn read_vec3i_from_u16s(buffer: &[u8], index: usize) -> Vec3i {
 #[inline]
 pub fn read_vec3i_from_u32s(buffer: &[u8], index: usize) -> Vec3i {
     Vec3i::new(
-        read_u32(&buffer, index    ) as usize,
-        read_u32(&buffer, index + 4) as usize,
-        read_u32(&buffer, index + 8) as usize
+        read_u32(buffer, index) as usize,
+        read_u32(buffer, index + 4) as usize,
+        read_u32(buffer, index + 8) as usize,
     )
 }
 
@@ -71,8 +87,8 @@ pub fn read_vec3i_from_u32s(buffer: &[u8], index: usize) -> Vec3i {
 #[inline]
 pub fn read_vec3i_from_u64s(buffer: &[u8], index: usize) -> Vec3i {
     Vec3i::new(
-        read_u64(&buffer, index     ) as usize,
-        read_u64(&buffer, index +  8) as usize,
-        read_u64(&buffer, index + 16) as usize
+        read_u64(buffer, index) as usize,
+        read_u64(buffer, index + 8) as usize,
+        read_u64(buffer, index + 16) as usize,
     )
-}
\ No newline at end of file
+}
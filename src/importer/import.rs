@@ -1,71 +1,148 @@
-use std::path::Path;
-use num::{NumCast, PrimInt, Unsigned, traits::Zero};
-use crate::matrix::*;
-use crate::importer::{load_xraw_as_matrix, XRawMatrix, Voxel, VoxelType, ImportError};
-use crate::schematic::{Schema, WireIndex, CompType, CompData};
 use crate::circuit::*;
-
+use crate::importer::{load_xraw_as_matrix, ImportError, XRawMatrix};
+use crate::matrix::*;
+use crate::schematic::Schema;
+use std::path::Path;
 
 const THRESHOLD: usize = 3;
 
-// read the xraw file at given location
-pub fn load_xraw_file<P: AsRef<Path>>(path: P) -> Result<Schema, ImportError> {
-    let matrix_result = match load_xraw_as_matrix(path) {
-        Ok (r) => r,
-        Err(e) => {return Err(e);}
-    };
-
-    match matrix_result {
-        XRawMatrix::Ind8 (matrix) => Ok(convert_matrix_to_schema(&matrix, &|v| v == 0u8      , THRESHOLD, &|v, a| match_index(v as usize, a))),
-        XRawMatrix::Ind16(matrix) => Ok(convert_matrix_to_schema(&matrix, &|v| v == 0xffffu16, THRESHOLD, &|v, a| match_index(v as usize, a))),
-        XRawMatrix::Vox8 (matrix, voxel_type) => {
-
-            Ok(Schema::new())
-        },
-        XRawMatrix::Vox16(matrix, voxel_type) => {
-
-            Ok(Schema::new())
-        },
-        XRawMatrix::Vox32(matrix, voxel_type) => {
-
-            Ok(Schema::new())
-        },
-        _ => Err(ImportError::Schema)
+// read the xraw file at given location, the wires carry data of the given width
+// only the palette indexes tell the components apart, colored voxels are rejected
+pub fn load_xraw_file<P: AsRef<Path>>(path: P, width: DataWidth) -> Result<Schema, ImportError> {
+    let convert = |v: usize, a| match_index(v, a, width);
+    match load_xraw_as_matrix(path)? {
+        XRawMatrix::Ind8(matrix) => Ok(convert_matrix_to_schema(
+            &matrix,
+            &|v| v == 0u8,
+            THRESHOLD,
+            &|v, a| convert(v as usize, a),
+        )
+        .with_width(width)),
+        XRawMatrix::Ind16(matrix) => Ok(convert_matrix_to_schema(
+            &matrix,
+            &|v| v == 0xffffu16,
+            THRESHOLD,
+            &|v, a| convert(v as usize, a),
+        )
+        .with_width(width)),
+        _ => Err(ImportError::Schema),
     }
 }
 
-
-// simply match indexes with component types
-fn match_index(value: usize, volume: usize) -> ToBuild {
+// simply match indexes with component types,
+// constants too large for the data width are clamped to all-ones
+fn match_index(value: usize, volume: usize, width: DataWidth) -> ElemType {
     // values from 1 to 16 are wires
-    if 1 <= value && value <= 16 {
-        return ToBuild::Wire((value - 1) as Channel);
+    if (1..=16).contains(&value) {
+        return ElemType::Wire((value - 1) as Channel);
     }
     match value {
-        17 => ToBuild::Gate(Operator::Or  ),
-        18 => ToBuild::Gate(Operator::And ),
-        19 => ToBuild::Gate(Operator::Nor ),
-        20 => ToBuild::Gate(Operator::Nand),
-        21 => ToBuild::Gate(Operator::Add ),
-        22 => ToBuild::Gate(Operator::Mul ),
-        23 => ToBuild::Gate(Operator::Min ),
-        24 => ToBuild::Gate(Operator::Max ),
-        25 => ToBuild::Mux,
-        26 => ToBuild::Demux(1),
-        27 => ToBuild::Constant((volume - 4) as Data),
-        28 => ToBuild::Bus,
-        29 => ToBuild::Keyboard,
-        _  => ToBuild::Empty,
+        17 => ElemType::Gate(Operator::Or),
+        18 => ElemType::Gate(Operator::And),
+        19 => ElemType::Gate(Operator::Nor),
+        20 => ElemType::Gate(Operator::Nand),
+        21 => ElemType::Gate(Operator::Add),
+        22 => ElemType::Gate(Operator::Mul),
+        23 => ElemType::Gate(Operator::Min),
+        24 => ElemType::Gate(Operator::Max),
+        25 => ElemType::Mux,
+        26 => ElemType::Demux(1),
+        27 => ElemType::Fixed(width.clamp(volume.saturating_sub(4) as Data)),
+        28 => ElemType::Bus,
+        29 => ElemType::Input,
+        30 => ElemType::Gate(Operator::Xor),
+        31 => ElemType::Gate(Operator::Xnor),
+        32 => ElemType::Gate(Operator::Not),
+        33 => ElemType::Gate(Operator::Sub),
+        34 => ElemType::Gate(Operator::Div),
+        35 => ElemType::Gate(Operator::Mod),
+        36 => ElemType::Gate(Operator::Shl),
+        37 => ElemType::Gate(Operator::Shr),
+        38 => ElemType::Gate(Operator::Eq),
+        39 => ElemType::Gate(Operator::Lt),
+        40 => ElemType::Gate(Operator::Gt),
+        41 => ElemType::Register,
+        42 => ElemType::Latch,
+        43 => ElemType::Toggle,
+        44 => ElemType::Memory(volume, true),
+        45 => ElemType::Memory(volume, false),
+        46 => ElemType::Led,
+        47 => ElemType::Digit,
+        48 => ElemType::Panel(16, 16),
+        49 => ElemType::Panel(8, 8),
+        // each half of the period lasts as many ticks as the volume above the minimum,
+        // the four indexes shift the phase by a quarter of the period
+        50..=53 => {
+            let half = volume.saturating_sub(4).max(1) as u32;
+            ElemType::Clock(ClockParams {
+                high: half,
+                low: half,
+                phase: (value - 50) as u32 * half / 2,
+            })
+        }
+        _ => ElemType::Empty,
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+    use std::fs;
 
-// return a function that detects if a voxel is empty based on the number of channels
-fn get_empty_voxel_function<T: Copy + Zero>(voxel_type: VoxelType) -> Box<dyn Fn(Voxel<T>) -> bool> {
-    match voxel_type {
-        VoxelType::Cr   => Box::new(move |v| v.r().is_zero()),
-        VoxelType::Crg  => Box::new(move |v| v.r().is_zero() && v.g().is_zero()),
-        VoxelType::Crgb => Box::new(move |v| v.r().is_zero() && v.r().is_zero() && v.b().is_zero()),
-        _               => Box::new(move |v| v.a().is_zero()),
+    // indexed xraw file of a single layer of voxels, given row by row
+    fn write_xraw(path: &str, rows: &[&[u8]]) {
+        let mut file = b"XRAW".to_vec();
+        file.extend_from_slice(&[0, 1, 8, 8]);
+        for side in [rows[0].len(), rows.len(), 1] {
+            file.extend_from_slice(&(side as u32).to_le_bytes());
+        }
+        file.extend_from_slice(&256u32.to_le_bytes());
+        file.extend(rows.concat());
+        fs::write(path, file).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn wires_poke_into_the_gate() {
+        // the input wire is surrounded by the gate, the gate surrounds the output wire
+        let path = temp_path("gate.xraw");
+        write_xraw(
+            &path,
+            &[&[17, 17, 17, 2, 2], &[1, 1, 17, 17, 2], &[17, 17, 17, 2, 2]],
+        );
+        let schema = load_xraw_file(&path, DataWidth(8)).unwrap();
+        assert!(schema.verify().is_ok());
+        assert_eq!(schema.width(), DataWidth(8));
+        assert_eq!(schema.wires().len(), 2);
+        assert_eq!(schema.comps().len(), 1);
+
+        let gate = &schema.comps()[0];
+        assert!(matches!(gate.comp_type, CompType::Gate(Operator::Or, _)));
+        let channel = |pins: &[Index]| schema.wires()[pins[0] as usize].channel;
+        assert_eq!(gate.pins_in.len(), 1);
+        assert_eq!(gate.pins_out.len(), 1);
+        assert_eq!(channel(&gate.pins_in), 0);
+        assert_eq!(channel(&gate.pins_out), 1);
+    }
+
+    #[test]
+    fn constants_are_clamped() {
+        let width = DataWidth(2);
+        assert!(matches!(match_index(27, 5, width), ElemType::Fixed(1)));
+        assert!(matches!(match_index(27, 12, width), ElemType::Fixed(3)));
+        assert!(matches!(
+            match_index(44, 9, width),
+            ElemType::Memory(9, true)
+        ));
+        assert!(matches!(match_index(54, 9, width), ElemType::Empty));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = temp_path("magic.xraw");
+        fs::write(&path, [0u8; 24]).unwrap();
+        let result = load_xraw_file(&path, DataWidth(8));
+        assert!(matches!(result, Err(ImportError::Magic(_))));
+    }
+}
@@ -1,12 +1,9 @@
-
 // https://eisenwave.github.io/voxel-compression-docs/related/voxel_formats.html
 
 mod base;
-mod xraw;
 mod import;
+mod xraw;
 
-use base::*;
-use xraw::*;
-use import::*;
-
-
+pub use base::*;
+pub use import::*;
+pub use xraw::*;
@@ -0,0 +1,11 @@
+//! Build logic circuits from voxel schematics and run them, in a bevy app or headless
+
+pub mod circuit;
+pub mod importer;
+pub mod math;
+pub mod matrix;
+pub mod netlist;
+pub mod schematic;
+pub mod simulator;
+pub mod snapshot;
+pub mod trace;
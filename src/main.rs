@@ -1,19 +1,69 @@
 //! Create a custom material to draw basic lines in 3D
 
 use bevy::prelude::*;
+use clap::Parser;
+use std::process;
 
-mod circuit;
-mod math;
-mod matrix;
-mod schematic;
+mod cli;
+mod headless;
 
-use circuit::*;
-use schematic::*;
+use bevy_logic_circuit::circuit::*;
+use bevy_logic_circuit::schematic::*;
+use bevy_logic_circuit::snapshot::{
+    load_state, save_state, start_history, HistoryCapacity, StateLoad, StateSave,
+};
+use bevy_logic_circuit::trace::{
+    dump_vcd, report_cycles, start_cycles, start_vcd, BreakpointHit, CycleWatch, VcdDump,
+};
 
 fn main() {
-    let schema = Schema::default();
+    let cli = cli::Cli::parse();
 
-    App::new()
+    let mut schema = cli.schema().unwrap_or_else(|e| {
+        let path = cli.input_file.clone().unwrap_or_default();
+        eprintln!("Cannot load {}: {}", path.display(), e);
+        process::exit(1);
+    });
+    for (port, backend) in cli.buses.iter() {
+        if !schema.set_bus_backend(*port, backend.clone()) {
+            eprintln!("No bus on port {}", port);
+            process::exit(1);
+        }
+    }
+
+    if cli.verify {
+        match schema.verify() {
+            Ok(warnings) => warnings.iter().for_each(|warning| eprintln!("{}", warning)),
+            Err(errors) => {
+                errors.iter().for_each(|error| eprintln!("{}", error));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(ticks) = cli.headless {
+        if let Err(errors) = headless::run(schema, &cli, ticks) {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            process::exit(1);
+        }
+        return;
+    }
+
+    let keymap = cli.key_map(schema.width()).unwrap_or_else(|e| {
+        eprintln!("Cannot load key map {}: {}", cli.keymap, e);
+        process::exit(1);
+    });
+
+    let mut scheduler = TickScheduler::default();
+    if let Some(rate) = cli.tick_rate() {
+        scheduler.rate = rate;
+    }
+
+    let mut app = App::new();
+    app
         // default plugins to display window and setup renderer
         .add_plugins(DefaultPlugins)
         // construct the circuitry from schematic
@@ -22,7 +72,53 @@ fn main() {
         //.add_startup_system(start_test)
         // add the systems that will run the circuitry
         .add_plugins(CircuitPlugin)
-        .run();
+        // replace the default speed and keys of the plugin
+        .insert_resource(scheduler)
+        .insert_resource(keymap);
+
+    if let Some(breakpoints) = cli.breakpoints() {
+        app.insert_resource(breakpoints)
+            .add_systems(Update, report_hits);
+    }
+    // the circuit resumes before its history starts
+    if let Some(path) = cli.load_state.clone() {
+        app.insert_resource(StateLoad(path))
+            .add_systems(PostStartup, load_state.before(start_history));
+    }
+    if let Some(path) = cli.save_state.clone() {
+        app.insert_resource(StateSave(path))
+            .add_systems(Last, save_state);
+    }
+    if let Some(capacity) = cli.history {
+        app.insert_resource(HistoryCapacity(capacity))
+            .add_systems(PostStartup, start_history);
+    }
+    if let Some(path) = cli.vcd.clone() {
+        let capacity = cli.vcd_ticks;
+        app.insert_resource(VcdDump { path, capacity })
+            .add_systems(PostStartup, start_vcd)
+            .add_systems(Last, dump_vcd);
+    }
+    if let Some(max_period) = cli.detect_cycles {
+        app.insert_resource(CycleWatch(max_period))
+            .add_systems(PostStartup, start_cycles)
+            .add_systems(Update, report_cycles);
+    }
+    if let Some(dir) = cli.dump_memories {
+        app.insert_resource(MemoryDump(dir))
+            .add_systems(Last, dump_memories);
+    }
+    app.run();
+}
+
+// tell which breakpoints paused the circuit
+fn report_hits(mut hits: EventReader<BreakpointHit>) {
+    for hit in hits.read() {
+        info!(
+            "Breakpoint {} at tick {}, {:?}",
+            hit.id, hit.tick, hit.condition
+        );
+    }
 }
 
 fn _start_test(
@@ -11,7 +11,7 @@ pub enum Axis {
     Z,
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize, PartialEq, Hash)]
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Vec3i {
     pub x: usize,
     pub y: usize,
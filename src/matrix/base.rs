@@ -1,4 +1,4 @@
-use crate::circuit::{Channel, Data, Operator};
+use crate::circuit::{Channel, ClockParams, Data, Operator};
 use crate::math::{Box3i, Vec3i};
 use serde::{Deserialize, Serialize};
 
@@ -106,4 +106,12 @@ pub enum ElemType {
     Demux(Data),
     Bus,
     Input,
+    Register,
+    Latch,
+    Toggle,
+    Memory(usize, bool),
+    Led,
+    Digit,
+    Panel(u8, u8),
+    Clock(ClockParams),
 }
@@ -8,9 +8,9 @@ pub fn find_connections(
     labels_amount: usize,
     threshold: usize,
 ) -> Csr<Label, ()> {
-    // prepare a graph with all the nodes
+    // prepare a graph with all the nodes, the first one stands for the empty cells
     let mut graph = Csr::<Label, ()>::new();
-    for label in 1..=labels_amount as Label {
+    for label in 0..=labels_amount as Label {
         graph.add_node(label);
     }
 
@@ -1,93 +1,117 @@
-use std::collections::HashMap;
-use petgraph::{csr::Csr, visit::IntoNeighbors};
+use super::*;
 use crate::circuit::*;
 use crate::schematic::*;
-
-mod base;
-
+use petgraph::{csr::Csr, visit::IntoNeighbors};
+use std::collections::HashMap;
 
 // generate a schema from the given matrix
-pub fn convert_matrix_to_schema<T: Clone + Copy + Eq + Default>
-(matrix: &Matrix<T>, is_empty: &FnEmpty<T>, threshold: usize, convert: &dyn Fn(T, usize) -> ElemType)
--> Schema {
-
+pub fn convert_matrix_to_schema<T: Clone + Copy + Eq + Default>(
+    matrix: &Matrix<T>,
+    is_empty: &FnEmpty<T>,
+    threshold: usize,
+    convert: &dyn Fn(T, usize) -> ElemType,
+) -> Schema {
     // from the matrix analysis, generate a schematic
-    let (graph, elements, models) = parse_matrix(matrix, is_empty, threshold);
+    let (graph, elements, mut models) = parse_matrix(matrix, is_empty, threshold);
 
     // build the reverse graph to find input wires
     let rev_graph = reverse_graph(&graph);
 
-    // generate the list of models
+    // generate the list of models in the order of the elements
     // keep track of the mapping between signature and index
-    let mut index = 0;
-    let mut signatures = HashMap::<Signature, ModelIndex>::with_capacity(models.len());
-    let mut model_list = Vec::<ModelData>::with_capacity(models.len());
-    for (signature, model) in models {
-        signatures.insert(signature, index);
-        model_list.push(model);
-        index += 1;
+    let mut signatures = HashMap::<Morph, Index>::with_capacity(models.len());
+    let mut model_list = Vec::<Model>::with_capacity(models.len());
+    for element in elements.iter() {
+        if let Some(model) = models.remove(&element.morph) {
+            signatures.insert(element.morph, model_list.len() as Index);
+            model_list.push(model);
+        }
     }
 
-    // generate the list of wires and other elements
-    let mut wire_list = Vec::<WireData>::with_capacity(elements.len());
-    let mut comp_list = Vec::<CompData>::with_capacity(elements.len());
-    for element in elements.iter() {
+    // convert each element, wires are numbered before being connected to the components
+    let types: Vec<ElemType> = elements
+        .iter()
+        .map(|element| convert(element.value, element.volume))
+        .collect();
+    let mut wire_indexes = HashMap::<Label, Index>::with_capacity(elements.len());
+    for (element, elem_type) in elements.iter().zip(types.iter()) {
+        if let ElemType::Wire(_) = elem_type {
+            wire_indexes.insert(element.label, wire_indexes.len() as Index);
+        }
+    }
 
+    // generate the list of wires and other elements
+    let mut wire_list = Vec::<SchemaWire>::with_capacity(wire_indexes.len());
+    let mut comp_list = Vec::<SchemaComp>::with_capacity(elements.len() - wire_indexes.len());
+    for (element, elem_type) in elements.iter().zip(types) {
         // prepare the model of the element
-        let model_attr = ModelAttr {
+        let model = ModelAttr {
             position: element.position,
-            index: signatures[&element.signature],
+            mesh_index: signatures[&element.morph],
         };
 
-        // find inputs and outputs
-        let pins_in : Vec<WireIndex> = rev_graph.neighbors(element.label).map(|v| v).collect();
-        let pins_out: Vec<WireIndex> = graph    .neighbors(element.label).map(|v| v).collect();
+        // only wires can be connected to the pins of a component
+        let pins = |graph: &Csr<Label, ()>| -> Vec<Index> {
+            graph
+                .neighbors(element.label)
+                .filter_map(|label| wire_indexes.get(&label).copied())
+                .collect()
+        };
 
         // convert element into schematic component
-        match convert(element.value, element.volume) {
-            ToBuild::Wire(channel) => {
-                wire_list.push(WireData {channel, model_attr});
-            },
-            ToBuild::Bus => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Bus});
-            },
-            ToBuild::Mux => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Mux});
-            },
-            ToBuild::Demux(data) => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Demux(data)});
-            },
-            ToBuild::Constant(data) => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Constant(data)});
-            },
-            ToBuild::Gate(op) => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Gate(op)});
-            },
-            ToBuild::Keyboard => {
-                comp_list.push(CompData{pins_in, pins_out, model_attr, comp_type: CompType::Keyboard});
-            },
-            _ => {},
-        }
+        let comp_type = match elem_type {
+            ElemType::Empty => continue,
+            ElemType::Wire(channel) => {
+                wire_list.push(SchemaWire {
+                    channel,
+                    resolution: None,
+                    model,
+                });
+                continue;
+            }
+            ElemType::Fixed(data) => CompType::Fixed(data),
+            ElemType::Gate(op) => CompType::Gate(op, OverflowMode::default()),
+            ElemType::Mux => CompType::Mux,
+            ElemType::Demux(data) => CompType::Demux(data),
+            ElemType::Bus => CompType::Bus(BusBackend::default()),
+            ElemType::Input => CompType::Input,
+            ElemType::Register => CompType::Register,
+            ElemType::Latch => CompType::Latch,
+            ElemType::Toggle => CompType::Toggle,
+            ElemType::Memory(size, writable) => CompType::Memory(MemoryParams {
+                size: size as u32,
+                writable,
+                image: None,
+            }),
+            ElemType::Led => CompType::Led,
+            ElemType::Digit => CompType::Digit,
+            ElemType::Panel(width, height) => CompType::Panel(width, height),
+            ElemType::Clock(params) => CompType::Clock(params),
+        };
+        comp_list.push(SchemaComp {
+            comp_type,
+            pins_in: pins(&rev_graph),
+            pins_out: pins(&graph),
+            delay: None,
+            model,
+        });
     }
 
-    return Schema {
-        wires      : wire_list,
-        components : comp_list,
-        models     : model_list,
-    }
+    Schema::new(wire_list, comp_list, model_list)
 }
 
-
 // the petgraph::visit::Reversed should have allowed to do this in one line of code...
 // but alas it doesn't work with the IntoNeighbors trait
 fn reverse_graph(graph: &Csr<Label, ()>) -> Csr<Label, ()> {
     let mut reversed = Csr::<Label, ()>::new();
-    let label_count = graph.node_count();
-    for label in 1..=label_count as Label {reversed.add_node(label);}
-    for l1 in 1..=label_count as Label {
+    let label_count = graph.node_count() as Label;
+    for label in 0..label_count {
+        reversed.add_node(label);
+    }
+    for l1 in 0..label_count {
         for l2 in graph.neighbors(l1) {
             reversed.add_edge(l2, l1, ());
         }
     }
-    return reversed;
-}
\ No newline at end of file
+    reversed
+}
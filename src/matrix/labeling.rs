@@ -93,15 +93,18 @@ pub fn connected_component_labeling<T: Clone + Copy + Eq + Default>(
     // convert the disjoint-set into a hashmap
     // to join labels into a single one
     let mut replace = HashMap::<Label, Label>::with_capacity(current as usize);
+    // the sets are numbered in the order they are first met in the matrix
+    let mut sets: Vec<_> = disjoint.sets().collect();
+    sets.sort_by_key(|set| set.iter().min().copied());
     let mut label: Label = 1;
-    for set in disjoint.sets() {
+    for set in sets {
         for elem in set {
             replace.insert(elem, label);
         }
         label += 1;
     }
     // simply replace each label by the new jointed one
-    for cell in labels.data.iter_mut() {
+    for cell in labels.data.iter_mut().filter(|cell| **cell > 0) {
         *cell = replace[cell];
     }
 
@@ -3,12 +3,14 @@
  */
 mod base;
 mod connectivity;
+mod converter;
 mod labeling;
 mod morphology;
 mod parser;
 
 pub use base::*;
 pub use connectivity::*;
+pub use converter::*;
 pub use labeling::*;
 pub use morphology::*;
 pub use parser::*;
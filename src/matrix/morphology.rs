@@ -2,7 +2,7 @@ use super::*;
 use crate::math::{Box3i, Vec3i};
 use crate::schematic;
 use bit_vec::BitVec;
-use block_mesh::ndshape::{ConstShape, ConstShape3u32};
+use block_mesh::ndshape::{RuntimeShape, Shape};
 use block_mesh::{
     greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
 };
@@ -25,7 +25,10 @@ pub fn find_bounding_boxes(matrix: &Matrix<Label>, labels_amount: usize) -> Vec<
             let index = (label - 1) as usize;
             let curr = Vec3i::new(x, y, z);
             let abox = boxes[index];
-            boxes[index] = Box3i::new(abox.begin.min(curr), abox.end.max(curr));
+            boxes[index] = Box3i::new(
+                abox.begin.min(curr),
+                abox.end.max(curr + Vec3i::new(1, 1, 1)),
+            );
         }
     });
     boxes
@@ -60,31 +63,36 @@ pub fn generate_morph(matrix: &Matrix<Label>, label: Label, abox: Box3i) -> (Mor
 // https://0fps.net/2012/06/30/meshing-in-a-minecraft-game/
 // https://github.com/bonsairobo/block-mesh-rs/blob/main/examples-crate/render/main.rs
 
-// we define the size of a chunk as a cube of 256 * 256 * 256
-// the total number of voxels in the cube needs to be smaller than a 2^32 anyway
-const SIZE: u32 = 0x100;
-type Chunk = ConstShape3u32<SIZE, SIZE, SIZE>;
+// the shape is surrounded by a layer of empty voxels for its faces to be found
+const BORDER: u32 = 1;
 
-// this function generate a trimesh from a matrix
-pub fn generate_model(matrix: &Matrix<Label>, label: Label) -> schematic::Model {
+// this function generate a trimesh of the label in its bounding box
+pub fn generate_model(matrix: &Matrix<Label>, label: Label, abox: Box3i) -> schematic::Model {
     // prepare buffer of boolean voxels
     // fill it with true if the given label is present
-    let mut voxels = [BoolVoxel(false); Chunk::SIZE as usize];
-    for i in 0..Chunk::SIZE {
-        let [x, y, z] = Chunk::delinearize(i);
-        voxels[i as usize] = BoolVoxel(matrix.get(x as usize, y as usize, z as usize) == label);
-    }
+    let size = abox.size();
+    let shape = RuntimeShape::<u32, 3>::new([
+        size.x as u32 + 2 * BORDER,
+        size.y as u32 + 2 * BORDER,
+        size.z as u32 + 2 * BORDER,
+    ]);
+    let mut voxels = vec![BoolVoxel(false); shape.size() as usize];
+    matrix.for_each_in_box(abox, &mut |x, y, z| {
+        let cell = Vec3i::new(x, y, z) - abox.begin;
+        let cell = [cell.x as u32, cell.y as u32, cell.z as u32].map(|v| v + BORDER);
+        voxels[shape.linearize(cell) as usize] = BoolVoxel(matrix.get(x, y, z) == label);
+    });
 
     // run the algorithm to find exposed faces
     let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
     let mut buffer = GreedyQuadsBuffer::new(voxels.len());
     greedy_quads(
-        &voxels,       // buffer of voxels to analyze
-        &Chunk {},     // chunk format
-        [0; 3],        // TODO: starting point
-        [SIZE - 1; 3], // TODO: end point
-        &faces,        // order of vertices on the face
-        &mut buffer,   // output buffer
+        &voxels,                               // buffer of voxels to analyze
+        &shape,                                // chunk format
+        [0; 3],                                // starting point
+        shape.as_array().map(|side| side - 1), // end point
+        &faces,                                // order of vertices on the face
+        &mut buffer,                           // output buffer
     );
 
     // prepare buffers to read data generated from the algorithm
@@ -94,12 +102,14 @@ pub fn generate_model(matrix: &Matrix<Label>, label: Label) -> schematic::Model
     let mut positions = Vec::with_capacity(num_vertices);
     let mut normals = Vec::with_capacity(num_vertices);
 
-    // fill the buffer with quads data
+    // fill the buffer with quads data, the border is removed from the positions
     let mut index: u32 = 0;
-    for (group, face) in buffer.quads.groups.into_iter().zip(faces.into_iter()) {
+    for (group, face) in buffer.quads.groups.into_iter().zip(faces) {
         for quad in group.into_iter() {
-            indexes.extend_from_slice(&face.quad_mesh_indices(index));
-            positions.extend_from_slice(&face.quad_mesh_positions(&quad, 1.0));
+            indexes.extend_from_slice(&face.quad_mesh_indices(index * 4));
+            for position in face.quad_mesh_positions(&quad, 1.0) {
+                positions.push(position.map(|v| v - BORDER as f32));
+            }
             normals.extend_from_slice(&face.quad_mesh_normals());
             index += 1;
         }
@@ -26,16 +26,19 @@ impl<T: Clone + Copy + Eq + Default> Element<T> {
     }
 }
 
+// connections between the elements, the elements and a model for each morphology
+pub type Parsed<T> = (
+    Csr<Label, ()>,
+    Vec<Element<T>>,
+    HashMap<Morph, schematic::Model>,
+);
+
 // parse the matrix and deduce data that will be used to make a schematic
 pub fn parse_matrix<T: Clone + Copy + Eq + Default>(
     matrix: &Matrix<T>,
     is_empty: &FnEmpty<T>,
     threshold: usize,
-) -> (
-    Csr<Label, ()>,
-    Vec<Element<T>>,
-    HashMap<Morph, schematic::Model>,
-) {
+) -> Parsed<T> {
     // generate a matrix with a label for each component
     let (labels_matrix, labels_mapping) = connected_component_labeling(matrix, is_empty);
     let labels_amount = labels_mapping.len();
@@ -61,9 +64,9 @@ pub fn parse_matrix<T: Clone + Copy + Eq + Default>(
         elements[index] = Element::<T>::new(label, value, abox.begin, volume, morph);
 
         // if the component has a new morphology, generate a model for it
-        //if !models.contains_key(&morph) {
-        //    models.insert(morph, generate_model(&labels_matrix, label, *abox));
-        //}
+        models
+            .entry(morph)
+            .or_insert_with(|| generate_model(&labels_matrix, label, *abox));
     }
     models.shrink_to_fit();
     (graph, elements, models)
@@ -0,0 +1,530 @@
+use crate::circuit::*;
+use crate::schematic::Index;
+use crate::snapshot::{CompState, History, Snapshot, SnapshotError};
+use crate::trace::{Breakpoints, CycleDetector, TraceRecorder};
+use std::collections::HashMap;
+
+// operation performed by a packed component
+#[derive(Clone, Copy)]
+pub enum Op {
+    Gate(Operator, OverflowMode),
+    Mux,
+    Demux(Data),
+    Fixed(Data),
+    Bus(u32),
+    Input,
+    // index of the state in the list of the netlist
+    Register(u32),
+    Latch(u32),
+    Toggle(u32),
+    TriState(u32),
+    Memory(u32),
+    Display(u32),
+    Clock(u32),
+}
+
+// a component packed as an operation and the range of its pins in the pin list,
+// inputs are in `begin..middle` and outputs in `middle..end`
+#[derive(Clone, Copy)]
+pub struct Record {
+    pub op: Op,
+    pub begin: u32,
+    pub middle: u32,
+    pub end: u32,
+    // index of the pipeline of components delayed by more than a tick
+    pub pipeline: Option<u32>,
+    // evaluated again on every pass while settling
+    pub settles: bool,
+}
+
+/* Netlist: wires as flat arrays and components as packed records */
+pub struct Netlist {
+    pub(super) prev: Vec<Data>,
+    pub(super) next: Vec<Data>,
+    pub(super) channels: Vec<Channel>,
+    pub(super) width: DataWidth,
+    pub(super) records: Vec<Record>,
+    pub(super) pins: Vec<Index>,
+    // data driven on each output pin, by position in the pin list
+    pub(super) outs: Vec<Data>,
+    // output pins of the disabled tri-state buffers, which drive nothing
+    pub(super) released: Vec<bool>,
+    // positions of the output pins writing to each wire, the drivers of a wire
+    // are in `driver_ranges[wire]..driver_ranges[wire + 1]`
+    pub(super) drivers: Vec<u32>,
+    pub(super) driver_ranges: Vec<u32>,
+    pub(super) resolutions: Vec<Resolution>,
+    // strict wires which drivers currently disagree and the conflicts not taken yet
+    pub(super) conflicting: HashMap<usize, Vec<Data>>,
+    pub(super) conflicts: Vec<DriverConflict>,
+    // passes over the combinational components while settling, and the loops which did not
+    pub(super) timing: TimingMode,
+    pub(super) settle_limit: usize,
+    pub(super) oscillations: Vec<Oscillation>,
+    pub(super) input: [Data; NB_CHANNELS],
+    pub(super) registers: Vec<CompRegister>,
+    pub(super) latches: Vec<CompLatch>,
+    pub(super) toggles: Vec<CompToggle>,
+    pub(super) tristates: Vec<CompTriState>,
+    pub(super) memories: Vec<CompMemory>,
+    pub(super) displays: Vec<DisplayState>,
+    pub(super) clocks: Vec<CompClock>,
+    pub(super) buses: Vec<CompIOBus>,
+    pub(super) pipelines: Vec<Pipeline>,
+    pub(super) bus_device: IOBusDevice,
+    pub(super) trace: Option<TraceRecorder>,
+    pub(super) breakpoints: Option<Breakpoints>,
+    pub(super) cycles: Option<CycleDetector>,
+    pub(super) history: Option<History>,
+    pub(super) ticks: u64,
+}
+
+impl Netlist {
+    // run the circuit for the given amount of ticks, stop early when a breakpoint fires,
+    // return the number of ticks executed
+    pub fn step(&mut self, n: u64) -> u64 {
+        // buffer reused by every gate to gather its input values
+        let mut values = Vec::<Data>::new();
+        for i in 0..n {
+            self.ticks += 1;
+            self.tick(&mut values);
+            if let Some(mut history) = self.history.take() {
+                self.record(&mut history);
+                self.history = Some(history);
+            }
+            if let Some(cycles) = &mut self.cycles {
+                let next = &self.next;
+                cycles.check(self.ticks, |i| next.get(i as usize).copied().unwrap_or(0));
+            }
+            if self.check_breakpoints() {
+                return i + 1;
+            }
+        }
+        n
+    }
+
+    // report the changes of the last tick to the history, memories only report
+    // the word at their address
+    fn record(&self, history: &mut History) {
+        for (i, (prev, next)) in self.prev.iter().zip(self.next.iter()).enumerate() {
+            history.wire(i as Index, *prev, *next);
+        }
+        for (channel, data) in self.input.iter().enumerate() {
+            history.input(channel as Channel, *data);
+        }
+        for (i, record) in self.records.iter().enumerate() {
+            if let Op::Memory(state) = record.op {
+                let pins_in = &self.pins[record.begin as usize..record.middle as usize];
+                let inputs = read_by_channel(pins_in, &self.prev, &self.channels);
+                let address = CompMemory::address(&inputs);
+                if let Some(word) = self.memories[state as usize].words.get(address) {
+                    history.word(i as Index, address, *word);
+                }
+            } else {
+                history.comp(i as Index, self.comp_state(record));
+            }
+            if let Some(pipeline) = record.pipeline {
+                history.pipeline(i as Index, &self.pipelines[pipeline as usize]);
+            }
+        }
+        history.commit(self.ticks);
+    }
+
+    // check the breakpoints against the data of the last tick
+    fn check_breakpoints(&mut self) -> bool {
+        let Self {
+            prev,
+            next,
+            records,
+            pins,
+            breakpoints,
+            ticks,
+            ..
+        } = self;
+        let Some(breakpoints) = breakpoints else {
+            return false;
+        };
+        let wire = |i: Index| Some((*prev.get(i as usize)?, *next.get(i as usize)?));
+        let comp = |i: Index| {
+            let record = records.get(i as usize)?;
+            let pins_out = &pins[record.middle as usize..record.end as usize];
+            Some(pins_out.iter().map(|o| next[*o as usize]))
+        };
+        !breakpoints.check(*ticks, wire, comp).is_empty()
+    }
+
+    // same semantics as a full sweep of the circuit systems
+    fn tick(&mut self, values: &mut Vec<Data>) {
+        std::mem::swap(&mut self.prev, &mut self.next);
+
+        // sample the data of the wires during the tick
+        if let Some(trace) = &mut self.trace {
+            let prev = &self.prev;
+            trace.record(self.ticks, |i| prev.get(i as usize).copied().unwrap_or(0));
+        }
+
+        self.evaluate(values, false);
+        self.resolve(values);
+        if self.timing == TimingMode::Settle {
+            self.settle(values);
+        }
+        self.check_conflicts(values);
+    }
+
+    // evaluate the components from the data of the wires during the tick,
+    // only the combinational ones without a delay of their own while settling
+    fn evaluate(&mut self, values: &mut Vec<Data>, settling: bool) {
+        let Self {
+            prev,
+            channels,
+            width,
+            records,
+            pins,
+            outs,
+            released,
+            input,
+            registers,
+            latches,
+            toggles,
+            tristates,
+            memories,
+            displays,
+            clocks,
+            buses,
+            pipelines,
+            bus_device,
+            ..
+        } = self;
+
+        for record in records.iter() {
+            if settling && !record.settles {
+                continue;
+            }
+            let pins_in = &pins[record.begin as usize..record.middle as usize];
+            let pins_out = &pins[record.middle as usize..record.end as usize];
+            let outs = &mut outs[record.middle as usize..record.end as usize];
+
+            match record.op {
+                Op::Gate(operator, overflow) => {
+                    values.clear();
+                    values.extend(pins_in.iter().map(|i| prev[*i as usize]));
+                    outs.fill(operator.eval(overflow, *width, values));
+                }
+                Op::Mux => {
+                    let mut data: Data = 0;
+                    for i in pins_in.iter() {
+                        let bit = if prev[*i as usize] != 0 { 1 } else { 0 };
+                        data |= bit << channels[*i as usize];
+                    }
+                    outs.fill(data);
+                }
+                Op::Demux(value) => {
+                    let mut data: Data = 0;
+                    pins_in.iter().for_each(|i| data |= prev[*i as usize]);
+                    for (out, o) in outs.iter_mut().zip(pins_out) {
+                        let bit = (data >> channels[*o as usize]) & 1;
+                        *out = if bit != 1 { value } else { 0 };
+                    }
+                }
+                Op::Fixed(value) => outs.fill(value),
+                Op::Input => {
+                    for (out, o) in outs.iter_mut().zip(pins_out) {
+                        *out = input[channels[*o as usize] as usize];
+                    }
+                }
+                Op::Bus(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let bus = &mut buses[state as usize];
+                    bus.update(&inputs, bus_device);
+                    for (out, o) in outs.iter_mut().zip(pins_out) {
+                        *out = bus.output(channels[*o as usize]);
+                    }
+                }
+                Op::Register(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let data = registers[state as usize].update(&inputs);
+                    outs.fill(data);
+                }
+                Op::Latch(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let data = latches[state as usize].update(&inputs);
+                    outs.fill(data);
+                }
+                Op::Toggle(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let data = toggles[state as usize].update(&inputs);
+                    outs.fill(data);
+                }
+                Op::TriState(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let tristate = &mut tristates[state as usize];
+                    outs.fill(tristate.update(&inputs));
+                    released[record.middle as usize..record.end as usize].fill(!tristate.enabled);
+                }
+                Op::Memory(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    let data = memories[state as usize].update(&inputs);
+                    outs.fill(data);
+                }
+                Op::Clock(state) => {
+                    let data = clocks[state as usize].update();
+                    outs.fill(data);
+                }
+                Op::Display(state) => {
+                    let inputs = read_by_channel(pins_in, prev, channels);
+                    displays[state as usize].update(&inputs);
+                }
+            }
+
+            // delayed components drive the data leaving their pipeline
+            if let Some(index) = record.pipeline {
+                let pipeline = &mut pipelines[index as usize];
+                if !pipeline.is_steady(outs) {
+                    pipeline.shift(outs);
+                }
+                outs.copy_from_slice(pipeline.driven());
+            }
+        }
+    }
+
+    // combine the drivers of every wire, only the bits of the data width are kept
+    fn resolve(&mut self, values: &mut Vec<Data>) {
+        for wire in 0..self.next.len() {
+            self.driven(wire, values);
+            self.next[wire] = self.resolutions[wire].resolve(values);
+        }
+    }
+
+    // gather the data driven on the wire by the pins which did not let go of it
+    fn driven(&self, wire: usize, values: &mut Vec<Data>) {
+        let mask = self.width.mask();
+        let range = self.driver_ranges[wire] as usize..self.driver_ranges[wire + 1] as usize;
+        values.clear();
+        let driving = self.drivers[range]
+            .iter()
+            .filter(|p| !self.released[**p as usize]);
+        values.extend(driving.map(|p| self.outs[*p as usize] & mask));
+    }
+
+    // report the strict wires which drivers disagree once the data of the tick is final,
+    // only new conflicts are reported
+    fn check_conflicts(&mut self, values: &mut Vec<Data>) {
+        for wire in 0..self.next.len() {
+            let resolution = self.resolutions[wire];
+            if resolution != Resolution::Strict {
+                continue;
+            }
+            self.driven(wire, values);
+            if !resolution.conflicts(values) {
+                self.conflicting.remove(&wire);
+            } else if self.conflicting.get(&wire) != Some(values) {
+                self.conflicting.insert(wire, values.clone());
+                self.conflicts.push(DriverConflict {
+                    wire: wire as Index,
+                    tick: self.ticks,
+                    values: values.clone(),
+                });
+            }
+        }
+    }
+
+    // evaluate the combinational components again until the wires stop changing, the wires
+    // changing on the second half of the passes are reported when they never do, the data
+    // of the wires at the start of the tick is kept as the one of the previous tick
+    fn settle(&mut self, values: &mut Vec<Data>) {
+        let start = self.prev.clone();
+        let mut wires = Vec::<Index>::new();
+        let mut settled = false;
+        for pass in 0..self.settle_limit {
+            let changed = (0..self.next.len()).filter(|i| self.prev[*i] != self.next[*i]);
+            let changed: Vec<Index> = changed.map(|i| i as Index).collect();
+            if changed.is_empty() {
+                settled = true;
+                break;
+            }
+            if pass >= self.settle_limit / 2 {
+                wires.extend(changed);
+            }
+            self.prev.copy_from_slice(&self.next);
+            self.evaluate(values, true);
+            self.resolve(values);
+        }
+
+        self.prev = start;
+        if !settled {
+            wires.sort_unstable();
+            wires.dedup();
+            self.oscillations.push(Oscillation {
+                tick: self.ticks,
+                wires,
+            });
+        }
+    }
+
+    // number of ticks executed since the circuit was compiled
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // number of wires in the circuit
+    pub fn wire_count(&self) -> usize {
+        self.next.len()
+    }
+
+    // number of bits carried by the wires
+    pub fn width(&self) -> DataWidth {
+        self.width
+    }
+
+    // channel of the wire
+    pub fn channel(&self, index: usize) -> Option<Channel> {
+        self.channels.get(index).copied()
+    }
+
+    // value driven on the wire by the last tick
+    pub fn read_wire(&self, index: usize) -> Option<Data> {
+        self.next.get(index).copied()
+    }
+
+    // words of a memory component, given its index in the schematic
+    pub fn memory(&self, comp: usize) -> Option<&[Data]> {
+        match self.records.get(comp)?.op {
+            Op::Memory(state) => Some(&self.memories[state as usize].words),
+            _ => None,
+        }
+    }
+
+    // what a display component shows, given its index in the schematic
+    pub fn display(&self, comp: usize) -> Option<&DisplayState> {
+        match self.records.get(comp)?.op {
+            Op::Display(state) => Some(&self.displays[state as usize]),
+            _ => None,
+        }
+    }
+
+    // take a snapshot of the wires and of the state of the components
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ticks: self.ticks,
+            prev: self.prev.clone(),
+            next: self.next.clone(),
+            input: self.input,
+            comps: self.records.iter().map(|r| self.comp_state(r)).collect(),
+            pipelines: self.records.iter().map(|r| self.pipeline(r)).collect(),
+        }
+    }
+
+    // resume from a snapshot, nothing is changed if it does not fit the circuit
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let current: Vec<_> = self.records.iter().map(|r| self.comp_state(r)).collect();
+        let pipelines: Vec<_> = self.records.iter().map(|r| self.pipeline(r)).collect();
+        snapshot.check(self.wire_count(), &current, &pipelines)?;
+
+        self.ticks = snapshot.ticks;
+        self.prev.copy_from_slice(&snapshot.prev);
+        self.next.copy_from_slice(&snapshot.next);
+        self.input = snapshot.input;
+        for (n, (record, state)) in self.records.iter().zip(snapshot.comps.iter()).enumerate() {
+            match (record.op, state) {
+                (Op::Register(i), CompState::Register(c)) => self.registers[i as usize] = *c,
+                (Op::Latch(i), CompState::Latch(c)) => self.latches[i as usize] = *c,
+                (Op::Toggle(i), CompState::Toggle(c)) => self.toggles[i as usize] = *c,
+                (Op::TriState(i), CompState::TriState(c)) => self.tristates[i as usize] = *c,
+                (Op::Memory(i), CompState::Memory(c)) => self.memories[i as usize] = c.clone(),
+                (Op::Clock(i), CompState::Clock(c)) => self.clocks[i as usize] = *c,
+                (Op::Bus(i), CompState::Bus(c)) => self.buses[i as usize] = *c,
+                (Op::Display(i), CompState::Display(c)) => self.displays[i as usize] = c.clone(),
+                _ => {}
+            }
+            if let (Some(i), Some(pipeline)) = (record.pipeline, &snapshot.pipelines[n]) {
+                self.pipelines[i as usize] = pipeline.clone();
+            }
+        }
+        Ok(())
+    }
+
+    // internal state of a packed component
+    fn comp_state(&self, record: &Record) -> CompState {
+        match record.op {
+            Op::Register(i) => CompState::Register(self.registers[i as usize]),
+            Op::Latch(i) => CompState::Latch(self.latches[i as usize]),
+            Op::Toggle(i) => CompState::Toggle(self.toggles[i as usize]),
+            Op::TriState(i) => CompState::TriState(self.tristates[i as usize]),
+            Op::Memory(i) => CompState::Memory(self.memories[i as usize].clone()),
+            Op::Clock(i) => CompState::Clock(self.clocks[i as usize]),
+            Op::Bus(i) => CompState::Bus(self.buses[i as usize]),
+            Op::Display(i) => CompState::Display(self.displays[i as usize].clone()),
+            _ => CompState::None,
+        }
+    }
+
+    // pipeline of a packed component delayed by more than a tick
+    fn pipeline(&self, record: &Record) -> Option<Pipeline> {
+        record.pipeline.map(|i| self.pipelines[i as usize].clone())
+    }
+
+    // replace the history of the last ticks, return the previous one
+    pub fn set_history(&mut self, history: Option<History>) -> Option<History> {
+        std::mem::replace(&mut self.history, history)
+    }
+
+    pub fn history_mut(&mut self) -> Option<&mut History> {
+        self.history.as_mut()
+    }
+
+    // replace the trace recorder, return the previous one
+    pub fn set_trace(&mut self, trace: Option<TraceRecorder>) -> Option<TraceRecorder> {
+        std::mem::replace(&mut self.trace, trace)
+    }
+
+    pub fn trace(&self) -> Option<&TraceRecorder> {
+        self.trace.as_ref()
+    }
+
+    // replace the cycle detector, return the previous one
+    pub fn set_cycle_detector(&mut self, cycles: Option<CycleDetector>) -> Option<CycleDetector> {
+        std::mem::replace(&mut self.cycles, cycles)
+    }
+
+    pub fn cycle_detector(&self) -> Option<&CycleDetector> {
+        self.cycles.as_ref()
+    }
+
+    pub fn cycle_detector_mut(&mut self) -> Option<&mut CycleDetector> {
+        self.cycles.as_mut()
+    }
+
+    // breakpoints checked after each tick, created on first use
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        self.breakpoints.get_or_insert_with(Breakpoints::default)
+    }
+
+    // conflicts on strict wires since the last call
+    pub fn take_conflicts(&mut self) -> Vec<DriverConflict> {
+        std::mem::take(&mut self.conflicts)
+    }
+
+    // combinational loops which did not settle since the last call
+    pub fn take_oscillations(&mut self) -> Vec<Oscillation> {
+        std::mem::take(&mut self.oscillations)
+    }
+
+    // set the value seen by input components on the given channel
+    pub fn write_input(&mut self, channel: Channel, data: Data) {
+        self.input[channel as usize] = data;
+    }
+
+    // replace the backend of a bus port, return false if there is no such port
+    pub fn set_bus_backend(&mut self, port: usize, backend: Box<dyn IoBackend>) -> bool {
+        self.bus_device.set(port, backend)
+    }
+}
+
+// read the input wires of a component by channel
+fn read_by_channel(pins_in: &[Index], prev: &[Data], channels: &[Channel]) -> ByChannel {
+    let mut inputs: ByChannel = [None; NB_CHANNELS];
+    for i in pins_in.iter() {
+        combine_channel(&mut inputs, channels[*i as usize], prev[*i as usize]);
+    }
+    inputs
+}
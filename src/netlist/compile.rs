@@ -0,0 +1,156 @@
+use super::*;
+use crate::circuit::*;
+use crate::schematic::*;
+use std::collections::HashMap;
+
+// turn a schematic into a compact netlist, the schematic is verified first
+pub fn compile(schema: &Schema) -> Result<Netlist, Vec<Error>> {
+    let prepared = schema.prepare()?;
+    let schema = prepared.schema();
+
+    let width = schema.width();
+    let nb_wires = schema.wires().len();
+    let channels = schema.wires().iter().map(|wire| wire.channel).collect();
+
+    // pack every component with its pins
+    let mut records = Vec::<Record>::with_capacity(schema.comps().len());
+    let mut pins = Vec::<Index>::new();
+    let mut registers = Vec::<CompRegister>::new();
+    let mut latches = Vec::<CompLatch>::new();
+    let mut toggles = Vec::<CompToggle>::new();
+    let mut tristates = Vec::<CompTriState>::new();
+    // loaded from their images when prepared, in the order of the components
+    let memories = prepared.memories().to_vec();
+    let mut nb_memories = 0;
+    let mut displays = Vec::<DisplayState>::new();
+    let mut clocks = Vec::<CompClock>::new();
+    let mut buses = Vec::<CompIOBus>::new();
+    let mut pipelines = Vec::<Pipeline>::new();
+    for (i, comp) in schema.comps().iter().enumerate() {
+        let op = match &comp.comp_type {
+            CompType::Gate(operator, overflow) => Op::Gate(*operator, *overflow),
+            CompType::Mux => Op::Mux,
+            CompType::Demux(val) => Op::Demux(*val),
+            CompType::Fixed(val) => Op::Fixed(*val),
+            CompType::Bus(_) => {
+                buses.push(CompIOBus::new(buses.len()));
+                Op::Bus(buses.len() as u32 - 1)
+            }
+            CompType::Input => Op::Input,
+            CompType::Register => {
+                registers.push(CompRegister::default());
+                Op::Register(registers.len() as u32 - 1)
+            }
+            CompType::Latch => {
+                latches.push(CompLatch::default());
+                Op::Latch(latches.len() as u32 - 1)
+            }
+            CompType::Toggle => {
+                toggles.push(CompToggle::default());
+                Op::Toggle(toggles.len() as u32 - 1)
+            }
+            CompType::TriState => {
+                tristates.push(CompTriState::default());
+                Op::TriState(tristates.len() as u32 - 1)
+            }
+            CompType::Memory(_) => {
+                nb_memories += 1;
+                Op::Memory(nb_memories - 1)
+            }
+            CompType::Clock(params) => {
+                clocks.push(CompClock::new(*params));
+                Op::Clock(clocks.len() as u32 - 1)
+            }
+            CompType::Led => {
+                displays.push(DisplayState::led());
+                Op::Display(displays.len() as u32 - 1)
+            }
+            CompType::Digit => {
+                displays.push(DisplayState::digit());
+                Op::Display(displays.len() as u32 - 1)
+            }
+            CompType::Panel(width, height) => {
+                displays.push(DisplayState::panel(*width, *height));
+                Op::Display(displays.len() as u32 - 1)
+            }
+            CompType::Subcircuit(_) => {
+                unreachable!("instances replaced by their parts when prepared")
+            }
+        };
+
+        // components without inputs do not read from any wire
+        let begin = pins.len() as u32;
+        if !matches!(op, Op::Fixed(_) | Op::Input | Op::Clock(_)) {
+            pins.extend(sort_by_channel(&comp.pins_in, schema.wires()));
+        }
+        let middle = pins.len() as u32;
+        pins.extend_from_slice(&comp.pins_out);
+        let end = pins.len() as u32;
+
+        // components delayed by more than a tick hold their data in a pipeline
+        let delay = schema.delay(i);
+        let pipeline = (delay > 1).then(|| {
+            pipelines.push(Pipeline::new(delay, comp.pins_out.len()));
+            pipelines.len() as u32 - 1
+        });
+
+        records.push(Record {
+            op,
+            begin,
+            middle,
+            end,
+            pipeline,
+            settles: schema.settles(i),
+        });
+    }
+
+    // list the output pins writing to each wire, in the order of the components
+    let mut wire_drivers: Vec<Vec<u32>> = vec![Vec::new(); nb_wires];
+    for record in records.iter() {
+        for position in record.middle..record.end {
+            wire_drivers[pins[position as usize] as usize].push(position);
+        }
+    }
+    let mut driver_ranges = vec![0];
+    driver_ranges.extend(wire_drivers.iter().scan(0, |end, drivers| {
+        *end += drivers.len() as u32;
+        Some(*end)
+    }));
+    let resolutions = (0..nb_wires).map(|i| schema.resolution(i)).collect();
+    let nb_buses = buses.len();
+
+    Ok(Netlist {
+        prev: vec![0; nb_wires],
+        next: vec![0; nb_wires],
+        channels,
+        width,
+        records,
+        outs: vec![0; pins.len()],
+        released: vec![false; pins.len()],
+        pins,
+        drivers: wire_drivers.concat(),
+        driver_ranges,
+        resolutions,
+        conflicting: HashMap::new(),
+        conflicts: Vec::new(),
+        timing: schema.timing(),
+        settle_limit: schema.settle_limit(),
+        oscillations: Vec::new(),
+        input: [0; NB_CHANNELS],
+        registers,
+        latches,
+        toggles,
+        tristates,
+        memories,
+        displays,
+        clocks,
+        buses,
+        pipelines,
+        bus_device: IOBusDevice::disconnected(nb_buses),
+        trace: None,
+        breakpoints: None,
+        cycles: None,
+        history: None,
+        ticks: 0,
+    })
+}
@@ -0,0 +1,31 @@
+/**
+ * Compiled simulation of logic circuits independent of the ECS
+ */
+mod base;
+mod compile;
+
+pub use base::*;
+pub use compile::compile;
+
+#[cfg(test)]
+mod tests {
+    use crate::schematic::testing::*;
+
+    #[test]
+    fn matches_ecs() {
+        for seed in 1..200 {
+            let input = random_input(seed);
+            let schema = random_schema(seed, seed % 2 == 0);
+            on_every_backend(&schema, |sim| {
+                write_inputs(sim, &input);
+                let wires: Vec<_> = (1..25)
+                    .map(|_| {
+                        sim.step(1);
+                        read_wires(sim)
+                    })
+                    .collect();
+                (wires, sim.ticks())
+            });
+        }
+    }
+}
@@ -1,48 +1,196 @@
 use crate::circuit::*;
 use crate::math::*;
+use crate::schematic::SubcircuitParams;
 /**
  * represent a model to load, build and to display in bevy
  */
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::{fs, io, path};
 
-// define index for components
-pub type Index = u32;
+// components and wires are given by their index in the schematic
+pub use crate::circuit::Index;
 
 // indicate position of the model and model to use
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct ModelAttr {
     pub position: Vec3i,
     pub mesh_index: Index,
 }
 
 // a wire of the schematic
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SchemaWire {
     pub channel: Channel,
+    // resolution of the schematic if none
+    pub resolution: Option<Resolution>,
     pub model: ModelAttr,
 }
 
 // the type of each element in the schematic
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum CompType {
-    Bus,
+    Bus(BusBackend),
     Mux,
     Demux(Data),
     Fixed(Data),
-    Gate(Operator),
+    Gate(Operator, OverflowMode),
+    Input,
+    Register,
+    Latch,
+    Toggle,
+    Memory(MemoryParams),
+    Led,
+    Digit,
+    // width and height of the panel in pixels
+    Panel(u8, u8),
+    Clock(ClockParams),
+    // instance of another schematic, replaced by its parts when flattened
+    Subcircuit(SubcircuitParams),
+    // passes its data through while enabled, lets go of its wires otherwise
+    TriState,
+}
+
+// type of a component whatever its parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompKind {
+    Bus,
+    Mux,
+    Demux,
+    Fixed,
+    Gate,
     Input,
+    Register,
+    Latch,
+    Toggle,
+    Memory,
+    Led,
+    Digit,
+    Panel,
+    Clock,
+    Subcircuit,
+    TriState,
+}
+
+impl CompType {
+    pub fn kind(&self) -> CompKind {
+        match self {
+            CompType::Bus(_) => CompKind::Bus,
+            CompType::Mux => CompKind::Mux,
+            CompType::Demux(_) => CompKind::Demux,
+            CompType::Fixed(_) => CompKind::Fixed,
+            CompType::Gate(..) => CompKind::Gate,
+            CompType::Input => CompKind::Input,
+            CompType::Register => CompKind::Register,
+            CompType::Latch => CompKind::Latch,
+            CompType::Toggle => CompKind::Toggle,
+            CompType::TriState => CompKind::TriState,
+            CompType::Memory(_) => CompKind::Memory,
+            CompType::Led => CompKind::Led,
+            CompType::Digit => CompKind::Digit,
+            CompType::Panel(..) => CompKind::Panel,
+            CompType::Clock(_) => CompKind::Clock,
+            CompType::Subcircuit(_) => CompKind::Subcircuit,
+        }
+    }
+
+    // whether the outputs only depend on the inputs of the same tick, without any state
+    pub fn is_combinational(&self) -> bool {
+        matches!(
+            self,
+            CompType::Gate(..) | CompType::Mux | CompType::Demux(_) | CompType::TriState
+        )
+    }
+}
+
+// streams a bus component exchanges bytes with
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub enum BusBackend {
+    // standard streams of the process
+    #[default]
+    Stdio,
+    // file to read from and file to write to
+    Files(String, String),
+    // unix domain socket listening on the path
+    Socket(String),
+}
+
+impl BusBackend {
+    // check that the backend can be opened without opening it
+    pub fn check(&self) -> io::Result<()> {
+        match self {
+            BusBackend::Stdio => Ok(()),
+            BusBackend::Files(input, output) => {
+                fs::metadata(input)?;
+                check_creatable(output)
+            }
+            BusBackend::Socket(path) if cfg!(unix) => check_creatable(path),
+            BusBackend::Socket(_) => Err(io::ErrorKind::Unsupported.into()),
+        }
+    }
+
+    // connect to the streams
+    pub fn open(&self) -> io::Result<Box<dyn IoBackend>> {
+        Ok(match self {
+            BusBackend::Stdio => Box::new(StdioBackend),
+            BusBackend::Files(input, output) => Box::new(FileBackend::open(input, output)?),
+            #[cfg(unix)]
+            BusBackend::Socket(path) => Box::new(SocketBackend::bind(path)?),
+            #[cfg(not(unix))]
+            BusBackend::Socket(_) => return Err(io::ErrorKind::Unsupported.into()),
+        })
+    }
+}
+
+// the directory of the file exists and the file is not a directory
+fn check_creatable(file: &str) -> io::Result<()> {
+    let file = path::Path::new(file);
+    if file.is_dir() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::metadata(dir).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+// parameters of a memory component
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MemoryParams {
+    // number of words, the length of the image is used if null
+    pub size: u32,
+    // read-only memories ignore their write channel
+    pub writable: bool,
+    // binary or hex file to fill the memory with
+    pub image: Option<String>,
 }
 
 // an element of the schematic
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SchemaComp {
     pub comp_type: CompType,
     pub pins_in: Vec<Index>,
     pub pins_out: Vec<Index>,
+    // ticks taken to drive the outputs, delay of the type if none
+    pub delay: Option<u32>,
     pub model: ModelAttr,
 }
 
+// entities of the wires ordered by their index in the schematic
+#[derive(Default, Resource)]
+pub struct SchemaWires(pub Vec<Entity>);
+
+// entities of the components ordered by their index in the schematic
+#[derive(Default, Resource)]
+pub struct SchemaComps(pub Vec<Entity>);
+
+// order input pins by the channel of their wire, pins on the same channel keep their order
+pub fn sort_by_channel(indexes: &[Index], wires: &[SchemaWire]) -> Vec<Index> {
+    let mut sorted = indexes.to_vec();
+    sorted.sort_by_key(|i| wires.get(*i as usize).map(|wire| wire.channel));
+    sorted
+}
+
 pub fn convert_wire_list(indexes: &[Index], entities: &[Entity]) -> Vec<Entity> {
     indexes.iter().map(|i| entities[*i as usize]).collect()
 }
@@ -0,0 +1,127 @@
+use crate::circuit::*;
+use crate::schematic::*;
+use serde::Deserialize;
+
+/* Format 0: schematics saved before the format had a version */
+// 16-bit data, gates without overflow mode, buses on the standard streams
+#[derive(Deserialize)]
+pub struct SchemaV0 {
+    wires: Vec<WireV0>,
+    comps: Vec<CompV0>,
+    models: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct WireV0 {
+    channel: Channel,
+    model: ModelAttr,
+}
+
+#[derive(Deserialize)]
+enum CompTypeV0 {
+    Bus,
+    Mux,
+    Demux(u16),
+    Fixed(u16),
+    Gate(Operator),
+    Input,
+}
+
+#[derive(Deserialize)]
+struct CompV0 {
+    comp_type: CompTypeV0,
+    pins_in: Vec<Index>,
+    pins_out: Vec<Index>,
+    model: ModelAttr,
+}
+
+impl SchemaV0 {
+    // same circuit in the current format, the defaults match the old behavior
+    pub fn upgrade(self) -> Schema {
+        let wires = self
+            .wires
+            .into_iter()
+            .map(|wire| SchemaWire {
+                channel: wire.channel,
+                resolution: None,
+                model: wire.model,
+            })
+            .collect();
+        let comps = self
+            .comps
+            .into_iter()
+            .map(|comp| SchemaComp {
+                comp_type: match comp.comp_type {
+                    CompTypeV0::Bus => CompType::Bus(BusBackend::Stdio),
+                    CompTypeV0::Mux => CompType::Mux,
+                    CompTypeV0::Demux(value) => CompType::Demux(value as Data),
+                    CompTypeV0::Fixed(value) => CompType::Fixed(value as Data),
+                    CompTypeV0::Gate(op) => CompType::Gate(op, OverflowMode::default()),
+                    CompTypeV0::Input => CompType::Input,
+                },
+                pins_in: comp.pins_in,
+                pins_out: comp.pins_out,
+                delay: None,
+                model: comp.model,
+            })
+            .collect();
+        Schema::new(wires, comps, self.models).with_width(DataWidth(16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3i;
+    use crate::schematic::testing::*;
+    use serde::Serialize;
+
+    // layout of the old format, only to write files of that format
+    type OldAttr = (Vec3i, Index);
+
+    #[derive(Serialize)]
+    struct OldSchema {
+        wires: Vec<(Channel, OldAttr)>,
+        comps: Vec<(OldType, Vec<Index>, Vec<Index>, OldAttr)>,
+        models: Vec<Model>,
+    }
+
+    #[derive(Serialize)]
+    enum OldType {
+        _Bus,
+        _Mux,
+        _Demux(u16),
+        Fixed(u16),
+        Gate(Operator),
+        _Input,
+    }
+
+    #[test]
+    fn loads_unversioned() {
+        let origin = (Vec3i::new(0, 0, 0), 0);
+        let old = OldSchema {
+            wires: vec![(0, origin), (1, origin), (0, origin)],
+            comps: vec![
+                (OldType::Fixed(0xfff0), vec![], vec![0], origin),
+                (OldType::Fixed(0x0020), vec![], vec![1], origin),
+                (OldType::Gate(Operator::Add), vec![0, 1], vec![2], origin),
+            ],
+            models: vec![model()],
+        };
+        let path = temp_path("v0.blc");
+        std::fs::write(&path, bincode::serialize(&old).unwrap()).unwrap();
+
+        // the old adder wraps around at 16 bits
+        let schema = Schema::load(&path).unwrap();
+        assert_eq!(schema.width(), DataWidth(16));
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            assert_eq!(sim.read_wire(2), Some(0x0010));
+        });
+
+        // saved again in the current format
+        schema.save(&path).unwrap();
+        let schema = Schema::load(&path).unwrap();
+        assert_eq!(schema.comps().len(), 3);
+    }
+}
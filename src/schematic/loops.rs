@@ -0,0 +1,196 @@
+use crate::math::Vec3i;
+use crate::schematic::*;
+
+/* Combinational Loop: wires feeding back into themselves through combinational components */
+// a loop is never settled by a sequential component or a component with a delay of its own,
+// it holds its data at best and toggles forever at worst, e.g. a Nor reading its own output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CombLoop {
+    // index in the schematic and position in the voxel grid of each wire and component,
+    // ordered by index
+    pub wires: Vec<(Index, Vec3i)>,
+    pub comps: Vec<(Index, Vec3i)>,
+}
+
+impl Schema {
+    // find the strongly connected wires of the graph of the combinational components,
+    // ordered by their first wire
+    pub fn combinational_loops(&self) -> Vec<CombLoop> {
+        let wires = self.wires();
+        let comps = self.comps();
+        let nb_wires = wires.len();
+
+        // each combinational component links its input wires to its output wires
+        let settling: Vec<usize> = (0..comps.len()).filter(|i| self.settles(*i)).collect();
+        let mut succs: Vec<Vec<usize>> = vec![Vec::new(); nb_wires];
+        for i in settling.iter() {
+            let comp = &comps[*i];
+            for pin_in in comp.pins_in.iter().filter(|p| (**p as usize) < nb_wires) {
+                let outs = comp.pins_out.iter().filter(|p| (**p as usize) < nb_wires);
+                succs[*pin_in as usize].extend(outs.map(|p| *p as usize));
+            }
+        }
+
+        // single wires only loop when they feed themselves
+        let mut loops = Vec::<CombLoop>::new();
+        let mut loop_of = vec![usize::MAX; nb_wires];
+        for mut component in strongly_connected(&succs) {
+            if component.len() == 1 && !succs[component[0]].contains(&component[0]) {
+                continue;
+            }
+            component.sort_unstable();
+            component.iter().for_each(|w| loop_of[*w] = loops.len());
+            let wires = component
+                .iter()
+                .map(|w| (*w as Index, wires[*w].model.position))
+                .collect();
+            loops.push(CombLoop {
+                wires,
+                comps: Vec::new(),
+            });
+        }
+
+        // a component is part of the loop its inputs and outputs are in, there cannot be
+        // two such loops or they would be a single one
+        let loop_in = |pins: &[Index]| -> Vec<usize> {
+            let wires = pins.iter().filter_map(|p| loop_of.get(*p as usize));
+            wires.copied().filter(|l| *l != usize::MAX).collect()
+        };
+        for i in settling {
+            let comp = &comps[i];
+            let outs = loop_in(&comp.pins_out);
+            if let Some(l) = loop_in(&comp.pins_in)
+                .into_iter()
+                .find(|l| outs.contains(l))
+            {
+                loops[l].comps.push((i as Index, comp.model.position));
+            }
+        }
+        loops.sort_by_key(|comb_loop| comb_loop.wires[0].0);
+        loops
+    }
+}
+
+// strongly connected components of a graph given the successors of each node,
+// Tarjan's algorithm with an explicit stack so that long chains do not overflow
+fn strongly_connected(succs: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let nb_nodes = succs.len();
+    let mut order = vec![usize::MAX; nb_nodes];
+    let mut lowest = vec![0; nb_nodes];
+    let mut on_stack = vec![false; nb_nodes];
+    let mut stack = Vec::<usize>::new();
+    let mut components = Vec::<Vec<usize>>::new();
+    let mut visited = 0;
+
+    // node being visited and position of the next successor to follow
+    let mut calls = Vec::<(usize, usize)>::new();
+    for root in 0..nb_nodes {
+        if order[root] != usize::MAX {
+            continue;
+        }
+        calls.push((root, 0));
+        while let Some((node, next)) = calls.pop() {
+            if next == 0 {
+                order[node] = visited;
+                lowest[node] = visited;
+                visited += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            }
+            if let Some(succ) = succs[node].get(next) {
+                calls.push((node, next + 1));
+                if order[*succ] == usize::MAX {
+                    calls.push((*succ, 0));
+                } else if on_stack[*succ] {
+                    lowest[node] = lowest[node].min(order[*succ]);
+                }
+                continue;
+            }
+
+            // every successor is done, the node may be the root of a component
+            if lowest[node] == order[node] {
+                let mut component = Vec::new();
+                while let Some(other) = stack.pop() {
+                    on_stack[other] = false;
+                    component.push(other);
+                    if other == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+            if let Some((parent, _)) = calls.last() {
+                lowest[*parent] = lowest[*parent].min(lowest[node]);
+            }
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Operator;
+    use crate::schematic::testing::*;
+
+    fn placed(mut comp: SchemaComp, x: usize) -> SchemaComp {
+        comp.model.position = Vec3i::new(x, 0, 0);
+        comp
+    }
+
+    #[test]
+    fn finds_loops() {
+        let mut wires: Vec<SchemaWire> = (0..7).map(|_| wire(0)).collect();
+        wires[3].model.position = Vec3i::new(1, 2, 3);
+        let schema = schema(
+            wires,
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                // a Nor reading its own output
+                placed(gate(Operator::Nor, vec![0, 1], vec![1]), 1),
+                // two gates feeding each other, the other output wire is not part of it
+                placed(gate(Operator::Or, vec![0, 2], vec![3]), 2),
+                placed(gate(Operator::And, vec![3], vec![4, 2]), 3),
+                gate(Operator::Or, vec![4], vec![5]),
+                // a register breaks the loop
+                gate(Operator::Not, vec![6], vec![5]),
+                comp(CompType::Register, vec![5], vec![6]),
+            ],
+        );
+        let origin = Vec3i::new(0, 0, 0);
+        let loops = schema.combinational_loops();
+        assert_eq!(
+            loops,
+            [
+                CombLoop {
+                    wires: vec![(1, origin)],
+                    comps: vec![(1, Vec3i::new(1, 0, 0))],
+                },
+                CombLoop {
+                    wires: vec![(2, origin), (3, Vec3i::new(1, 2, 3))],
+                    comps: vec![(2, Vec3i::new(2, 0, 0)), (3, Vec3i::new(3, 0, 0))],
+                },
+            ]
+        );
+        let warnings = schema.verify().unwrap();
+        assert!(warnings.contains(&Warning::CombinationalLoop(1, 1)));
+        assert!(warnings.contains(&Warning::CombinationalLoop(2, 2)));
+
+        // delayed components are not combinational anymore
+        let schema = schema.with_delay(CompKind::Gate, 2);
+        assert!(schema.combinational_loops().is_empty());
+    }
+
+    #[test]
+    fn long_chains() {
+        let nb_wires = 100_000;
+        let wires = (0..nb_wires).map(|_| wire(0)).collect();
+        let comps = (0..nb_wires)
+            .map(|i| gate(Operator::Or, vec![i], vec![(i + 1) % nb_wires]))
+            .collect();
+        let loops = schema(wires, comps).combinational_loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].wires.len(), nb_wires as usize);
+        assert_eq!(loops[0].comps.len(), nb_wires as usize);
+    }
+}
@@ -2,11 +2,18 @@
  * Plugin for running logic circuits
  */
 mod base;
+mod legacy;
+mod loops;
 mod material;
 mod model;
 mod schema;
+mod subcircuit;
+#[cfg(test)]
+pub mod testing;
 
 pub use base::*;
+use legacy::SchemaV0;
 pub use material::MaterialStore;
 pub use model::Model;
 pub use schema::*;
+pub use subcircuit::SubcircuitParams;
@@ -3,16 +3,78 @@ use crate::schematic::*;
 /**
  * represent a model to load, build and to display in bevy
  */
-use bevy::prelude::*;
+use bevy::{app::AppExit, prelude::*};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::{error, fmt, fs, io, path};
 
 // indicate position of the model and model to use
-#[derive(Default, Serialize, Deserialize, Resource)]
+#[derive(Default, Clone, Serialize, Deserialize, Resource)]
 pub struct Schema {
+    width: DataWidth,
+    resolution: Resolution,
+    timing: TimingMode,
+    // delay of the components of each type without their own
+    type_delays: Vec<(CompKind, u32)>,
+    // named wires other schematics connect to when they instantiate this one
+    ports: Vec<(String, Index)>,
     wires: Vec<SchemaWire>,
     comps: Vec<SchemaComp>,
     models: Vec<Model>,
+    // directory of the file the schematic was loaded from, relative paths start there
+    #[serde(skip)]
+    dir: Option<path::PathBuf>,
+}
+
+// a verified schematic without subcircuits along with the memories of its components,
+// the circuit is built from it without reading any file again
+pub struct Prepared<'a> {
+    schema: Cow<'a, Schema>,
+    // loaded from their images, in the order of the components
+    memories: Vec<CompMemory>,
+    warnings: Vec<Warning>,
+}
+
+// files read while preparing a schematic, each one is read once however many
+// components refer to it
+#[derive(Default)]
+pub(super) struct Loader {
+    schemas: HashMap<path::PathBuf, Option<Rc<Schema>>>,
+    // subcircuits of another width are refused before their images are read,
+    // so every image is read with the same width
+    images: HashMap<path::PathBuf, Option<Rc<Vec<Data>>>>,
+}
+
+impl Loader {
+    // schematic of the file, none if it cannot be loaded
+    pub(super) fn schema(&mut self, path: path::PathBuf) -> Option<Rc<Schema>> {
+        let load = |path: &path::PathBuf| Schema::load(path).ok().map(Rc::new);
+        self.schemas.entry(path).or_insert_with_key(load).clone()
+    }
+
+    // words of the image file, none if it cannot be loaded
+    fn image(&mut self, path: path::PathBuf, width: DataWidth) -> Option<Rc<Vec<Data>>> {
+        let load = |path: &path::PathBuf| load_image(path, width).ok().map(Rc::new);
+        self.images.entry(path).or_insert_with_key(load).clone()
+    }
+}
+
+// start of the files with a version, never the start of an unversioned file
+// as it would be a count of wires beyond the size of any memory
+const FORMAT_MAGIC: [u8; 8] = *b"blcircu\xff";
+// version of the files written, increased on every change of the layout
+const FORMAT_VERSION: u32 = 1;
+
+// the file is in a version of the format this build does not know
+#[derive(Debug)]
+pub struct FormatError(pub u32);
+impl error::Error for FormatError {}
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown File Format Error, version={}", self.0)
+    }
 }
 
 // error types when analyzing a schematic
@@ -23,6 +85,18 @@ pub enum Error {
     CompModel(usize, Index),
     PinIn(usize, usize),
     PinOut(usize, usize),
+    MemoryImage(usize),
+    MemorySize(usize),
+    BusBackend(usize),
+    DataWidth(u32),
+    CompValue(usize, Data),
+    CompDelay(usize, u32),
+    Display(usize),
+    PortWire(String),
+    // with the errors of the schematic of the instance
+    Subcircuit(usize, Vec<Error>),
+    SubcircuitPort(usize, String),
+    SubcircuitPins(usize),
 }
 impl error::Error for Error {}
 impl fmt::Display for Error {
@@ -33,19 +107,250 @@ impl fmt::Display for Error {
             Self::CompModel(n, i) => write!(f, "Component Model Error at {}, index={}", n, i),
             Self::PinIn(n, i) => write!(f, "Pin Input Error at {}, {}", n, i),
             Self::PinOut(n, i) => write!(f, "Pin Output Error at {}, {}", n, i),
+            Self::MemoryImage(n) => write!(f, "Memory Image Error at {}", n),
+            Self::MemorySize(n) => write!(f, "Memory Size Error at {}", n),
+            Self::BusBackend(n) => write!(f, "Bus Backend Error at {}", n),
+            Self::DataWidth(w) => write!(f, "Data Width Error, width={}", w),
+            Self::CompValue(n, v) => write!(f, "Component Value Error at {}, value={}", n, v),
+            Self::CompDelay(n, d) => write!(f, "Component Delay Error at {}, delay={}", n, d),
+            Self::Display(n) => write!(f, "Display Error at {}", n),
+            Self::PortWire(p) => write!(f, "Port Wire Error, port={}", p),
+            Self::Subcircuit(n, errors) => {
+                let errors: Vec<String> = errors.iter().map(Error::to_string).collect();
+                write!(
+                    f,
+                    "Subcircuit Error at {}, errors=[{}]",
+                    n,
+                    errors.join("; ")
+                )
+            }
+            Self::SubcircuitPort(n, p) => write!(f, "Subcircuit Port Error at {}, port={}", n, p),
+            Self::SubcircuitPins(n) => write!(f, "Subcircuit Pins Error at {}", n),
+        }
+    }
+}
+
+// issues of a schematic which do not prevent building the circuit
+#[derive(Debug, PartialEq, Eq)]
+pub enum Warning {
+    // the image of the memory is longer than its size, with the number of words dropped
+    MemoryImage(usize, usize),
+    // no component writes to the wire, it stays null
+    NoDriver(usize),
+    // several components write to the wire, with the number of drivers
+    MultipleDrivers(usize, usize),
+    // wires feeding back into themselves through combinational components,
+    // with the first wire of the loop and its number of wires
+    CombinationalLoop(usize, usize),
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MemoryImage(n, w) => write!(f, "Memory Image Warning at {}, dropped={}", n, w),
+            Self::NoDriver(n) => write!(f, "No Driver Warning at {}", n),
+            Self::MultipleDrivers(n, d) => {
+                write!(f, "Multiple Drivers Warning at {}, drivers={}", n, d)
+            }
+            Self::CombinationalLoop(n, w) => {
+                write!(f, "Combinational Loop Warning at {}, wires={}", n, w)
+            }
         }
     }
 }
 
 impl Schema {
-    // check that the schema is valid before building the circuit
-    pub fn verify(&self) -> Result<(), Vec<Error>> {
+    // assemble a schematic from its parts, it still needs to be verified
+    pub fn new(wires: Vec<SchemaWire>, comps: Vec<SchemaComp>, models: Vec<Model>) -> Self {
+        Self {
+            width: DataWidth::default(),
+            resolution: Resolution::default(),
+            timing: TimingMode::default(),
+            type_delays: Vec::new(),
+            ports: Vec::new(),
+            wires,
+            comps,
+            models,
+            dir: None,
+        }
+    }
+
+    // path of a file the schematic refers to, relative to the file of the schematic
+    pub fn locate(&self, file: &str) -> path::PathBuf {
+        match &self.dir {
+            Some(dir) => dir.join(file),
+            None => path::PathBuf::from(file),
+        }
+    }
+
+    // same schematic with the relative paths starting from the directory of the other one
+    pub(super) fn relative_to(&self, parent: &Schema) -> Self {
+        Self {
+            dir: parent.dir.clone(),
+            ..self.clone()
+        }
+    }
+
+    // carry data of the given width on the wires instead of the default one
+    pub fn with_width(mut self, width: DataWidth) -> Self {
+        self.width = width;
+        self
+    }
+
+    // number of bits carried by the wires
+    pub fn width(&self) -> DataWidth {
+        self.width
+    }
+
+    // combine the drivers of the wires without their own resolution with the given one
+    pub fn with_resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    // how the drivers of the wire are combined
+    pub fn resolution(&self, wire: usize) -> Resolution {
+        self.wires
+            .get(wire)
+            .and_then(|wire| wire.resolution)
+            .unwrap_or(self.resolution)
+    }
+
+    // evaluate the circuit with the given timing instead of a tick per component
+    pub fn with_timing(mut self, timing: TimingMode) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    // when the data evaluated by the components reaches the wires
+    pub fn timing(&self) -> TimingMode {
+        self.timing
+    }
+
+    // passes over the combinational components after which the circuit is said to
+    // oscillate, enough to settle a circuit as deep as the number of such components
+    pub fn settle_limit(&self) -> usize {
+        (0..self.comps.len()).filter(|i| self.settles(*i)).count() + EXTRA_PASSES
+    }
+
+    // whether the component is evaluated again on every pass while settling,
+    // the combinational ones without a delay of their own
+    pub fn settles(&self, comp: usize) -> bool {
+        let combinational = self
+            .comps
+            .get(comp)
+            .is_some_and(|c| c.comp_type.is_combinational());
+        combinational && self.delay(comp) <= 1
+    }
+
+    // delay the components of the type, whatever their parameters,
+    // unless they have their own delay
+    pub fn with_delay(mut self, kind: CompKind, delay: u32) -> Self {
+        self.type_delays.retain(|(other, _)| *other != kind);
+        self.type_delays.push((kind, delay));
+        self
+    }
+
+    // ticks taken by the component to drive its outputs, a single one by default
+    pub fn delay(&self, comp: usize) -> u32 {
+        let Some(comp) = self.comps.get(comp) else {
+            return 1;
+        };
+        let kind = comp.comp_type.kind();
+        comp.delay.unwrap_or_else(|| {
+            self.type_delays
+                .iter()
+                .find(|(other, _)| *other == kind)
+                .map_or(1, |(_, delay)| *delay)
+        })
+    }
+
+    // name the wire so that other schematics can connect to it, a name is given once
+    pub fn with_port(mut self, name: &str, wire: Index) -> Self {
+        self.ports.retain(|(other, _)| other != name);
+        self.ports.push((name.to_string(), wire));
+        self
+    }
+
+    // wire of the named port
+    pub fn port(&self, name: &str) -> Option<Index> {
+        self.ports
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, wire)| *wire)
+    }
+
+    // named wires, in the order they were given
+    pub fn ports(&self) -> &[(String, Index)] {
+        &self.ports
+    }
+
+    // number of components writing to each wire
+    pub fn driver_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.wires.len()];
+        for comp in self.comps.iter() {
+            for pin in comp.pins_out.iter() {
+                if let Some(count) = counts.get_mut(*pin as usize) {
+                    *count += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    // wires ordered by their index
+    pub fn wires(&self) -> &[SchemaWire] {
+        &self.wires
+    }
+
+    // components of the schematic
+    pub fn comps(&self) -> &[SchemaComp] {
+        &self.comps
+    }
+
+    // models of the wires and components
+    pub fn models(&self) -> &[Model] {
+        &self.models
+    }
+
+    // check that the schema is valid before building the circuit,
+    // return the issues which do not prevent building it
+    pub fn verify(&self) -> Result<Vec<Warning>, Vec<Error>> {
+        self.prepare().map(|prepared| prepared.warnings)
+    }
+
+    // verify the schematic and read the files the circuit is built from
+    pub fn prepare(&self) -> Result<Prepared<'_>, Vec<Error>> {
+        self.prepare_at(&mut Loader::default(), 0)
+    }
+
+    // prepare the schematic instantiated at the depth given
+    pub(super) fn prepare_at(
+        &self,
+        loader: &mut Loader,
+        depth: usize,
+    ) -> Result<Prepared<'_>, Vec<Error>> {
         let mut errors = Vec::<Error>::new();
+        let mut warnings = Vec::<Warning>::new();
 
         let nb_wires = self.wires.len();
         let nb_models = self.models.len();
 
+        // check that the data fits in the wires
+        if !self.width.is_valid() {
+            errors.push(Error::DataWidth(self.width.0));
+        }
+        let mask = self.width.mask();
+        let mut memories = Vec::<CompMemory>::new();
+
         // check that wires are valid
+        let drivers = self.driver_counts();
+        let mut buses = vec![true; nb_wires];
+        for comp in self.comps.iter() {
+            if !matches!(comp.comp_type, CompType::TriState) {
+                let pins = comp.pins_out.iter().filter(|p| (**p as usize) < nb_wires);
+                pins.for_each(|p| buses[*p as usize] = false);
+            }
+        }
         for (i, wire) in self.wires.iter().enumerate() {
             // check that the channel of the wire is valid
             if wire.channel as usize >= NB_CHANNELS {
@@ -55,6 +360,21 @@ impl Schema {
             if wire.model.mesh_index as usize >= nb_models {
                 errors.push(Error::WireModel(i, wire.model.mesh_index));
             }
+            // report floating wires and wires written by several components,
+            // buses shared by tri-state buffers are meant to have several
+            match drivers[i] {
+                0 => warnings.push(Warning::NoDriver(i)),
+                1 => {}
+                _ if buses[i] => {}
+                n => warnings.push(Warning::MultipleDrivers(i, n)),
+            }
+        }
+
+        // check that the ports are on existing wires
+        for (name, wire) in self.ports.iter() {
+            if *wire as usize >= nb_wires {
+                errors.push(Error::PortWire(name.clone()));
+            }
         }
 
         // check that all elements are valid
@@ -77,19 +397,104 @@ impl Schema {
                     errors.push(Error::PinOut(i, j));
                 }
             }
+            // check that the component drives its outputs after at least one tick
+            let delay = self.delay(i);
+            // tri-state buffers let go of their wires on the tick they are disabled
+            let delayed = matches!(elem.comp_type, CompType::TriState) && delay > 1;
+            if delay == 0 || delay > MAX_DELAY || delayed {
+                errors.push(Error::CompDelay(i, delay));
+            }
+            // check that displays drive no wire and that a panel has at most a row
+            // per channel and a pixel per bit
+            match elem.comp_type {
+                CompType::Led | CompType::Digit if !elem.pins_out.is_empty() => {
+                    errors.push(Error::Display(i))
+                }
+                CompType::Panel(width, height)
+                    if !elem.pins_out.is_empty()
+                        || width as u32 > self.width.0
+                        || height as usize > NB_CHANNELS =>
+                {
+                    errors.push(Error::Display(i))
+                }
+                _ => {}
+            }
+            // check that constant values fit in the data width
+            if let CompType::Fixed(value) | CompType::Demux(value) = elem.comp_type {
+                if value & !mask != 0 {
+                    errors.push(Error::CompValue(i, value));
+                }
+            }
+            // check that the image of memories can be loaded and that they have words,
+            // but not too many to be allocated
+            if let CompType::Memory(params) = &elem.comp_type {
+                match self.load_memory(i, params, loader) {
+                    Ok((memory, dropped)) => {
+                        if dropped > 0 {
+                            warnings.push(Warning::MemoryImage(i, dropped));
+                        }
+                        memories.push(memory);
+                    }
+                    Err(e) => errors.push(e),
+                }
+            }
+            // check that the streams of buses can be opened
+            if let CompType::Bus(backend) = &elem.comp_type {
+                if backend.check().is_err() {
+                    errors.push(Error::BusBackend(i));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // check that the subcircuits can be instantiated, then report the loops which
+        // may never settle, including the ones through the parts of subcircuits
+        let (flat, memories) = self.flatten(memories, loader, depth)?;
+        for comb_loop in flat.combinational_loops() {
+            let first = comb_loop.wires[0].0 as usize;
+            warnings.push(Warning::CombinationalLoop(first, comb_loop.wires.len()));
         }
 
         // the schema is valid it can be used to generate a circuit
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        Ok(Prepared {
+            schema: flat,
+            memories,
+            warnings,
+        })
+    }
+
+    // allocate the memory filled with its image, with the number of words of the image
+    // which do not fit, the memory must have words but not too many to be allocated
+    fn load_memory(
+        &self,
+        index: usize,
+        params: &MemoryParams,
+        loader: &mut Loader,
+    ) -> Result<(CompMemory, usize), Error> {
+        let image = match &params.image {
+            Some(file) => loader
+                .image(self.locate(file), self.width)
+                .ok_or(Error::MemoryImage(index))?,
+            None => Rc::default(),
+        };
+        let size = match params.size {
+            0 => image.len(),
+            n => n as usize,
+        };
+        if size == 0 || size > MEMORY_MAX_SIZE {
+            return Err(Error::MemorySize(index));
         }
+        let dropped = image.len().saturating_sub(size);
+        Ok((CompMemory::new(size, params.writable, &image), dropped))
     }
 
     // load a file to generate a valid schematic
     pub fn load<P: AsRef<path::Path>>(path: P) -> Result<Self, Box<dyn error::Error>> {
         // try to open the file in read
+        let path = path.as_ref();
         let mut file = match fs::File::open(path) {
             Ok(f) => f,
             Err(e) => return Err(Box::new(e)),
@@ -101,14 +506,26 @@ impl Schema {
             return Err(Box::new(e));
         }
 
-        // generate the schematic from the file
-        let schema = match bincode::deserialize::<Schema>(&buffer) {
-            Ok(s) => s,
-            Err(e) => return Err(Box::new(e)),
+        // generate the schematic from the file, upgrading the files without a version
+        let schema = match buffer.strip_prefix(&FORMAT_MAGIC) {
+            Some(versioned) => {
+                let (version, body): (u32, Schema) = bincode::deserialize(versioned)?;
+                if version != FORMAT_VERSION {
+                    return Err(Box::new(FormatError(version)));
+                }
+                body
+            }
+            None => match bincode::deserialize::<SchemaV0>(&buffer) {
+                Ok(s) => s.upgrade(),
+                Err(e) => return Err(Box::new(e)),
+            },
         };
 
         // schema has passed all the checks, can be returned
-        Ok(schema)
+        Ok(Self {
+            dir: path.parent().map(path::Path::to_path_buf),
+            ..schema
+        })
     }
 
     // save to a file
@@ -119,11 +536,11 @@ impl Schema {
             Err(e) => return Err(Box::new(e)),
         };
 
-        // try to serialize the schematic
-        let buffer: Vec<u8> = match bincode::serialize(&self) {
-            Ok(b) => b,
-            Err(e) => return Err(Box::new(e)),
-        };
+        // try to serialize the schematic after the version of the format
+        let mut buffer = FORMAT_MAGIC.to_vec();
+        if let Err(e) = bincode::serialize_into(&mut buffer, &(FORMAT_VERSION, self)) {
+            return Err(Box::new(e));
+        }
 
         // write to the file
         if let Err(e) = io::Write::write(&mut file, &buffer) {
@@ -132,6 +549,178 @@ impl Schema {
 
         Ok(())
     }
+
+    // replace the backend of a bus, the port of a bus is its rank among the buses
+    // of the schematic, return false if there is no such port
+    pub fn set_bus_backend(&mut self, port: usize, backend: BusBackend) -> bool {
+        let mut buses = self
+            .comps
+            .iter_mut()
+            .filter_map(|comp| match &mut comp.comp_type {
+                CompType::Bus(backend) => Some(backend),
+                _ => None,
+            });
+        match buses.nth(port) {
+            Some(slot) => {
+                *slot = backend;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<'a> Prepared<'a> {
+    // the schematic with the parts of its subcircuits instead of the instances
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    // the schematic and the memories of its components, leaving the warnings
+    pub(super) fn into_parts(self) -> (Cow<'a, Schema>, Vec<CompMemory>) {
+        (self.schema, self.memories)
+    }
+
+    // memories loaded from their images, in the order of the components
+    pub fn memories(&self) -> &[CompMemory] {
+        &self.memories
+    }
+
+    // issues which do not prevent building the circuit
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    // connect every bus to its backend, the port of a bus is its rank among the buses,
+    // the buses of subcircuits come after the ones of the schematic
+    pub fn open_buses(&self) -> Result<Vec<Box<dyn IoBackend>>, Vec<Error>> {
+        let mut ports = Vec::<Box<dyn IoBackend>>::new();
+        let mut errors = Vec::<Error>::new();
+        for (i, comp) in self.schema.comps.iter().enumerate() {
+            if let CompType::Bus(backend) = &comp.comp_type {
+                match backend.open() {
+                    Ok(port) => ports.push(port),
+                    Err(_) => errors.push(Error::BusBackend(i)),
+                }
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(ports),
+            false => Err(errors),
+        }
+    }
+
+    // spawn the wires and components of the circuit without any model,
+    // the buses are left disconnected until their backends are opened,
+    // keep track of the entities by their index in the schematic
+    pub fn spawn(self, commands: &mut Commands) {
+        let mut memories = self.memories.into_iter();
+        let schema: &Schema = &self.schema;
+
+        // generate list of wires
+        let wires: Vec<Entity> = schema
+            .wires
+            .iter()
+            .enumerate()
+            .map(|(i, wire)| {
+                let channel = PinChannel(wire.channel);
+                commands
+                    .spawn((channel, schema.resolution(i), DataPrev(0), DataNext(0)))
+                    .id()
+            })
+            .collect();
+
+        // keep track of which components read from and write to each wire
+        let mut fanouts: Vec<Vec<Entity>> = vec![Vec::new(); wires.len()];
+        let mut drivers: Vec<Vec<(Entity, usize)>> = vec![Vec::new(); wires.len()];
+        let mut comps = Vec::<Entity>::with_capacity(schema.comps.len());
+        let mut nb_buses = 0;
+
+        // generate list of elements
+        for (i, comp) in schema.comps.iter().enumerate() {
+            let operands = sort_by_channel(&comp.pins_in, &schema.wires);
+            let pins_in = PinsIn(convert_wire_list(&operands, &wires));
+            let pins_out = PinsOut(convert_wire_list(&comp.pins_out, &wires));
+            let data_out = DataOut::new(comp.pins_out.len());
+
+            let id = match &comp.comp_type {
+                CompType::Gate(op, overflow) => {
+                    commands.spawn((*op, *overflow, pins_in, pins_out, data_out))
+                }
+                CompType::Mux => commands.spawn((CompMux {}, pins_in, pins_out, data_out)),
+                CompType::Demux(val) => {
+                    commands.spawn((CompDemux(*val), pins_in, pins_out, data_out))
+                }
+                CompType::Fixed(val) => commands.spawn((CompFixed(*val), pins_out, data_out)),
+                CompType::Bus(_) => {
+                    let bus = CompIOBus::new(nb_buses);
+                    nb_buses += 1;
+                    commands.spawn((bus, pins_in, pins_out, data_out))
+                }
+                CompType::Input => commands.spawn((CompInput {}, pins_out, data_out)),
+                CompType::Register => {
+                    let register = CompRegister::default();
+                    commands.spawn((register, pins_in, pins_out, data_out))
+                }
+                CompType::Latch => {
+                    let latch = CompLatch::default();
+                    commands.spawn((latch, pins_in, pins_out, data_out))
+                }
+                CompType::Toggle => {
+                    let toggle = CompToggle::default();
+                    commands.spawn((toggle, pins_in, pins_out, data_out))
+                }
+                CompType::TriState => {
+                    let tristate = CompTriState::default();
+                    commands.spawn((tristate, pins_in, pins_out, data_out))
+                }
+                CompType::Clock(params) => {
+                    commands.spawn((CompClock::new(*params), pins_out, data_out))
+                }
+                CompType::Led => commands.spawn((DisplayState::led(), pins_in)),
+                CompType::Digit => commands.spawn((DisplayState::digit(), pins_in)),
+                CompType::Panel(width, height) => {
+                    commands.spawn((DisplayState::panel(*width, *height), pins_in))
+                }
+                CompType::Memory(_) => {
+                    let memory = memories.next().expect("memories loaded when prepared");
+                    commands.spawn((memory, pins_in, pins_out, data_out))
+                }
+                CompType::Subcircuit(_) => {
+                    unreachable!("instances replaced by their parts when prepared")
+                }
+            }
+            .id();
+            comps.push(id);
+
+            // components delayed by more than a tick hold their data in a pipeline
+            let delay = schema.delay(i);
+            if delay > 1 {
+                let pipeline = Pipeline::new(delay, comp.pins_out.len());
+                commands.entity(id).insert(pipeline);
+            }
+
+            for pin in comp.pins_in.iter() {
+                fanouts[*pin as usize].push(id);
+            }
+            for (slot, pin) in comp.pins_out.iter().enumerate() {
+                drivers[*pin as usize].push((id, slot));
+            }
+        }
+
+        // attach the connections to the wires
+        for ((wire, fanout), driver) in wires.iter().zip(fanouts).zip(drivers) {
+            commands
+                .entity(*wire)
+                .insert((Fanout(fanout), Drivers(driver)));
+        }
+        commands.insert_resource(schema.width);
+        commands.insert_resource(schema.timing);
+        commands.insert_resource(Settling::new(schema.settle_limit()));
+        commands.insert_resource(IOBusDevice::disconnected(nb_buses));
+        commands.insert_resource(SchemaWires(wires));
+        commands.insert_resource(SchemaComps(comps));
+    }
 }
 
 // build the whole circuit
@@ -141,47 +730,55 @@ pub fn build_circuit(
     //materials: Res<MaterialStore>,
     schema: Res<Schema>,
 ) {
-    // store generated mesh handles in a simple vector
-    let models: Vec<Handle<Mesh>> = schema
+    // store generated mesh handles in a simple vector, they are not drawn yet
+    let _models: Vec<Handle<Mesh>> = schema
         .models
         .iter()
         .map(|model| meshes.add(model.to_mesh()))
         .collect();
 
-    // generate list of wires
-    let wires: Vec<Entity> = schema
-        .wires
-        .iter()
-        .map(|wire| {
-            commands
-                .spawn((PinChannel(wire.channel), DataPrev(0), DataNext(0)))
-                .id()
-        })
-        .collect();
-
-    // generate list of elements
-    for comp in schema.comps.iter() {
-        let pins_in = PinsIn(convert_wire_list(&comp.pins_in, &wires));
-        let pins_out = PinsOut(convert_wire_list(&comp.pins_out, &wires));
-
-        match comp.comp_type {
-            CompType::Gate(op) => {
-                commands.spawn((op, pins_in, pins_out));
+    // spawn the logic of the circuit connected to the backends of its buses,
+    // nothing is spawned from an invalid schematic
+    match schema
+        .prepare()
+        .and_then(|prepared| Ok((prepared.open_buses()?, prepared)))
+    {
+        Ok((ports, prepared)) => {
+            for warning in prepared.warnings() {
+                warn!("{}", warning);
             }
-            CompType::Mux => {
-                commands.spawn((CompMux {}, pins_in, pins_out));
-            }
-            CompType::Demux(val) => {
-                commands.spawn((CompDemux(val), pins_in, pins_out));
-            }
-            CompType::Fixed(val) => {
-                commands.spawn((CompFixed(val), pins_out));
-            }
-            CompType::Bus => {
-                commands.spawn((CompIOBus {}, pins_in, pins_out));
+            prepared.spawn(&mut commands);
+            commands.insert_resource(IOBusDevice::new(ports));
+        }
+        Err(errors) => {
+            for error in errors {
+                error!("{}", error);
             }
-            CompType::Input => {
-                commands.spawn((CompInput {}, pins_out));
+        }
+    }
+}
+
+// directory the memories are written to when the app exits
+#[derive(Resource)]
+pub struct MemoryDump(pub path::PathBuf);
+
+// write every memory to a hex file named after its index in the schematic,
+// so that their content can be checked after a run
+pub fn dump_memories(
+    mut exit: EventReader<AppExit>,
+    dump: Res<MemoryDump>,
+    width: Res<DataWidth>,
+    comps: Option<Res<SchemaComps>>,
+    memory_query: Query<&CompMemory>,
+) {
+    let (Some(_), Some(comps)) = (exit.read().next(), comps) else {
+        return;
+    };
+    for (i, entity) in comps.0.iter().enumerate() {
+        if let Ok(memory) = memory_query.get(*entity) {
+            let path = dump.0.join(format!("memory-{}.hex", i));
+            if let Err(e) = save_image(&path, &memory.words, *width) {
+                error!("Cannot dump memory {}: {}", i, e);
             }
         }
     }
@@ -0,0 +1,441 @@
+use super::schema::Loader;
+use crate::circuit::CompMemory;
+use crate::schematic::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::rc::Rc;
+
+// subcircuits instantiate other subcircuits up to this depth,
+// a schematic instantiating itself never reaches the bottom
+pub const SUBCIRCUIT_MAX_DEPTH: usize = 16;
+
+// where the schematic of a subcircuit comes from
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SubcircuitSource {
+    // schematic file loaded when the circuit is built
+    File(String),
+    Embedded(Box<Schema>),
+}
+
+// wires, components and memories an instance is replaced by
+type Parts = (Vec<SchemaWire>, Vec<SchemaComp>, Vec<CompMemory>);
+
+// parameters of a subcircuit component, the ports of its schematic named in `inputs`
+// and `outputs` are connected to the input and output pins of the instance, in order
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SubcircuitParams {
+    pub source: SubcircuitSource,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+impl Schema {
+    // replace every subcircuit by the parts of its schematic, the wires and components
+    // of the schematic keep their index, an instance is replaced by its first part and
+    // its other parts come after the components, every part takes the model of its
+    // instance so that a single model is drawn for all of them, the memories follow
+    // the order of the components
+    pub(super) fn flatten(
+        &self,
+        memories: Vec<CompMemory>,
+        loader: &mut Loader,
+        depth: usize,
+    ) -> Result<(Cow<'_, Schema>, Vec<CompMemory>), Vec<Error>> {
+        let is_instance = |comp: &SchemaComp| matches!(comp.comp_type, CompType::Subcircuit(_));
+        if !self.comps().iter().any(is_instance) {
+            return Ok((Cow::Borrowed(self), memories));
+        }
+
+        // the resolution and delay of the parts do not depend on the schematic anymore
+        let mut wires: Vec<SchemaWire> = self
+            .wires()
+            .iter()
+            .enumerate()
+            .map(|(w, wire)| SchemaWire {
+                resolution: Some(self.resolution(w)),
+                ..wire.clone()
+            })
+            .collect();
+
+        // each component along with its memory, if it is one
+        let with_memory = |comps: Vec<SchemaComp>, memories: Vec<CompMemory>| {
+            let mut memories = memories.into_iter();
+            comps.into_iter().map(move |comp| {
+                let memory = match comp.comp_type {
+                    CompType::Memory(_) => memories.next(),
+                    _ => None,
+                };
+                (comp, memory)
+            })
+        };
+        let mut comps = Vec::<(SchemaComp, Option<CompMemory>)>::new();
+        let mut appended = Vec::<(SchemaComp, Option<CompMemory>)>::new();
+        let mut errors = Vec::<Error>::new();
+        let outer = with_memory(self.comps().to_vec(), memories);
+        for (i, (comp, memory)) in outer.enumerate() {
+            let CompType::Subcircuit(params) = &comp.comp_type else {
+                let delay = Some(self.delay(i));
+                comps.push((SchemaComp { delay, ..comp }, memory));
+                continue;
+            };
+            match self.instantiate(i, params, loader, depth, wires.len()) {
+                Ok((parts_wires, parts_comps, parts_memories)) => {
+                    wires.extend(parts_wires);
+                    let mut parts = with_memory(parts_comps, parts_memories);
+                    // an instance without components leaves an unconnected constant
+                    let empty = SchemaComp {
+                        comp_type: CompType::Fixed(0),
+                        pins_in: Vec::new(),
+                        pins_out: Vec::new(),
+                        delay: None,
+                        ..comp
+                    };
+                    comps.push(parts.next().unwrap_or((empty, None)));
+                    appended.extend(parts);
+                }
+                Err(e) => errors.extend(e),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let (comps, memories): (Vec<_>, Vec<_>) = comps.into_iter().chain(appended).unzip();
+        let memories = memories.into_iter().flatten().collect();
+
+        let mut flat = Schema::new(wires, comps, self.models().to_vec())
+            .with_width(self.width())
+            .with_timing(self.timing());
+        for (name, wire) in self.ports() {
+            flat = flat.with_port(name, *wire);
+        }
+        Ok((Cow::Owned(flat), memories))
+    }
+
+    // wires, components and memories of the instance, the wires of its ports are replaced
+    // by the pins of the instance and the other ones are numbered from `nb_wires`
+    fn instantiate(
+        &self,
+        index: usize,
+        params: &SubcircuitParams,
+        loader: &mut Loader,
+        depth: usize,
+        nb_wires: usize,
+    ) -> Result<Parts, Vec<Error>> {
+        let instance = &self.comps()[index];
+        let failed = |errors| vec![Error::Subcircuit(index, errors)];
+        if depth >= SUBCIRCUIT_MAX_DEPTH {
+            return Err(failed(Vec::new()));
+        }
+        let loaded: Rc<Schema>;
+        let embedded: Schema;
+        let inner = match &params.source {
+            SubcircuitSource::File(file) => {
+                loaded = loader
+                    .schema(self.locate(file))
+                    .ok_or_else(|| failed(Vec::new()))?;
+                &*loaded
+            }
+            SubcircuitSource::Embedded(schema) => {
+                embedded = schema.relative_to(self);
+                &embedded
+            }
+        };
+
+        // the schematic must carry data of the same width and be valid on its own
+        if inner.width() != self.width() {
+            return Err(failed(vec![Error::DataWidth(inner.width().0)]));
+        }
+        let (inner, memories) = inner
+            .prepare_at(loader, depth + 1)
+            .map_err(failed)?
+            .into_parts();
+
+        // a port is connected to a single pin on the same channel
+        let mut errors = Vec::<Error>::new();
+        let mut mapping: Vec<Option<Index>> = vec![None; inner.wires().len()];
+        for (names, pins) in [
+            (&params.inputs, &instance.pins_in),
+            (&params.outputs, &instance.pins_out),
+        ] {
+            if names.len() != pins.len() {
+                errors.push(Error::SubcircuitPins(index));
+                continue;
+            }
+            for (name, pin) in names.iter().zip(pins.iter()) {
+                let port = inner.port(name).map(|wire| wire as usize);
+                match (port, self.wires().get(*pin as usize)) {
+                    (Some(wire), Some(outer))
+                        if inner.wires()[wire].channel == outer.channel
+                            && mapping[wire].is_none_or(|other| other == *pin) =>
+                    {
+                        mapping[wire] = Some(*pin);
+                    }
+                    _ => errors.push(Error::SubcircuitPort(index, name.clone())),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut wires = Vec::<SchemaWire>::new();
+        for (w, wire) in inner.wires().iter().enumerate() {
+            if mapping[w].is_none() {
+                mapping[w] = Some((nb_wires + wires.len()) as Index);
+                wires.push(SchemaWire {
+                    channel: wire.channel,
+                    resolution: Some(inner.resolution(w)),
+                    model: instance.model,
+                });
+            }
+        }
+        let remap = |pins: &[Index]| -> Vec<Index> {
+            let wires = pins.iter().map(|pin| mapping[*pin as usize]);
+            wires.map(Option::unwrap_or_default).collect()
+        };
+        let comps = inner
+            .comps()
+            .iter()
+            .enumerate()
+            .map(|(j, part)| SchemaComp {
+                comp_type: part.comp_type.clone(),
+                pins_in: remap(&part.pins_in),
+                pins_out: remap(&part.pins_out),
+                delay: Some(inner.delay(j)),
+                model: instance.model,
+            })
+            .collect();
+        Ok((wires, comps, memories))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Data, Operator};
+    use crate::math::Vec3i;
+    use crate::schematic::testing::*;
+    use std::path;
+
+    // full adder of the wires a, b and cin, with the sum and the carry
+    fn full_adder() -> Schema {
+        schema(
+            (0..8).map(|_| wire(0)).collect(),
+            vec![
+                gate(Operator::Xor, vec![0, 1], vec![5]),
+                gate(Operator::Xor, vec![5, 2], vec![3]),
+                gate(Operator::And, vec![0, 1], vec![6]),
+                gate(Operator::And, vec![5, 2], vec![7]),
+                gate(Operator::Or, vec![6, 7], vec![4]),
+            ],
+        )
+        .with_port("a", 0)
+        .with_port("b", 1)
+        .with_port("cin", 2)
+        .with_port("sum", 3)
+        .with_port("cout", 4)
+    }
+
+    fn adder(source: SubcircuitSource, pins_in: Vec<Index>, pins_out: Vec<Index>) -> SchemaComp {
+        let params = SubcircuitParams {
+            source,
+            inputs: vec!["a".into(), "b".into(), "cin".into()],
+            outputs: vec!["sum".into(), "cout".into()],
+        };
+        comp(CompType::Subcircuit(params), pins_in, pins_out)
+    }
+
+    #[test]
+    fn ripple_adder() {
+        let path = temp_path("adder.blc");
+        full_adder().save(&path).unwrap();
+        let embedded = || SubcircuitSource::Embedded(Box::new(full_adder()));
+        let file = || SubcircuitSource::File(path.to_string());
+
+        // two bits of a and b, the carry in, the two bits of the sum and the carries
+        let ripple = |a: Data, b: Data| {
+            let mut low = adder(embedded(), vec![0, 2, 4], vec![5, 7]);
+            low.model.position = Vec3i::new(1, 0, 0);
+            schema(
+                (0..9).map(|_| wire(0)).collect(),
+                vec![
+                    comp(CompType::Fixed(a & 1), vec![], vec![0]),
+                    comp(CompType::Fixed(a >> 1), vec![], vec![1]),
+                    comp(CompType::Fixed(b & 1), vec![], vec![2]),
+                    comp(CompType::Fixed(b >> 1), vec![], vec![3]),
+                    low,
+                    adder(file(), vec![1, 3, 7], vec![6, 8]),
+                ],
+            )
+        };
+
+        for (a, b) in (0..16).map(|n| (n & 3, n >> 2)) {
+            let schema = ripple(a, b);
+            assert!(schema
+                .verify()
+                .unwrap()
+                .iter()
+                .all(|w| *w == Warning::NoDriver(4)));
+            // the instances are replaced by their parts
+            let prepared = schema.prepare().unwrap();
+            let flat = prepared.schema();
+            assert_eq!(flat.comps().len(), 4 + 2 * 5);
+            assert_eq!(flat.wires().len(), 9 + 2 * 3);
+            assert_eq!(flat.comps()[4].model.position, Vec3i::new(1, 0, 0));
+
+            let sum = on_every_backend(&schema, |sim| {
+                sim.step(10);
+                let bits = [5, 6, 8].map(|wire| sim.read_wire(wire).unwrap());
+                bits[0] | bits[1] << 1 | bits[2] << 2
+            });
+            assert_eq!(sum, a + b, "{} + {}", a, b);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_instances() {
+        let instance = |source, inputs: &[&str], nb_pins: usize| {
+            let params = SubcircuitParams {
+                source,
+                inputs: inputs.iter().map(|name| name.to_string()).collect(),
+                outputs: vec!["sum".into()],
+            };
+            comp(CompType::Subcircuit(params), vec![0; nb_pins], vec![1])
+        };
+        let embedded = |schema| SubcircuitSource::Embedded(Box::new(schema));
+        let names = ["a", "b", "cin"];
+
+        // a schematic instantiating itself
+        let looping = temp_path("looping.blc");
+        let file = SubcircuitSource::File(looping.to_string());
+        schema(
+            vec![wire(0), wire(0)],
+            vec![instance(file.clone(), &names, 3)],
+        )
+        .save(&looping)
+        .unwrap();
+        let missing = SubcircuitSource::File(temp_path("missing.blc").to_string());
+        let wide = full_adder().with_width(crate::circuit::DataWidth(8));
+        let other_channel = schema(
+            vec![wire(1), wire(0)],
+            vec![gate(Operator::Or, vec![0], vec![1])],
+        )
+        .with_port("a", 0)
+        .with_port("sum", 1);
+
+        let schema = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                instance(embedded(full_adder()), &names, 3),
+                instance(embedded(full_adder()), &["a", "b", "c"], 3),
+                instance(embedded(full_adder()), &["a", "b"], 3),
+                instance(file, &names, 3),
+                instance(missing, &names, 3),
+                instance(embedded(wide), &names, 3),
+                instance(embedded(other_channel), &["a"], 1),
+            ],
+        );
+        let errors = schema.verify().unwrap_err();
+        assert!(
+            matches!(
+                &errors[..],
+                [
+                    Error::SubcircuitPort(1, c),
+                    Error::SubcircuitPins(2),
+                    Error::Subcircuit(3, looping),
+                    Error::Subcircuit(4, missing),
+                    Error::Subcircuit(5, wide),
+                    Error::SubcircuitPort(6, a),
+                ] if c == "c"
+                    && a == "a"
+                    && matches!(looping[..], [Error::Subcircuit(0, _)])
+                    && missing.is_empty()
+                    && matches!(wide[..], [Error::DataWidth(8)])
+            ),
+            "{:?}",
+            errors
+        );
+
+        // ports are checked like pins
+        let errors = full_adder().with_port("carry", 8).verify().unwrap_err();
+        assert!(matches!(&errors[..], [Error::PortWire(port)] if port == "carry"));
+    }
+
+    #[test]
+    fn file_next_to_schematic() {
+        // relative paths start from the directory of the schematic instantiating the file
+        let adder_path = temp_path("next_adder.blc");
+        full_adder().save(&adder_path).unwrap();
+        let file = path::Path::new(&*adder_path).file_name().unwrap();
+        let source = || SubcircuitSource::File(file.to_string_lossy().into_owned());
+        let path = temp_path("next_ripple.blc");
+        schema(
+            (0..6).map(|_| wire(0)).collect(),
+            vec![
+                comp(CompType::Fixed(1), vec![], vec![0]),
+                adder(source(), vec![0, 0, 1], vec![2, 3]),
+                adder(source(), vec![2, 3, 1], vec![4, 5]),
+            ],
+        )
+        .save(&path)
+        .unwrap();
+        let schema = Schema::load(&path).unwrap();
+        assert_eq!(schema.prepare().unwrap().schema().comps().len(), 1 + 2 * 5);
+        let wires = on_every_backend(&schema, |sim| {
+            sim.step(10);
+            [2, 3, 4, 5].map(|wire| sim.read_wire(wire).unwrap())
+        });
+        // 1 + 1 then 0 + 1, the carry in is left floating
+        assert_eq!(wires, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn components_keep_their_index() {
+        let rom = |words: &str, name: &str| {
+            let path = temp_path(name);
+            std::fs::write(&path, words).unwrap();
+            let params = MemoryParams {
+                size: 0,
+                writable: false,
+                image: Some(path.to_string()),
+            };
+            (CompType::Memory(params), path)
+        };
+        let (inner_rom, _inner_image) = rom("0003 0004", "inner.hex");
+        let (outer_rom, _outer_image) = rom("0001 0002", "outer.hex");
+        let inner = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                comp(CompType::Fixed(7), vec![], vec![0]),
+                comp(inner_rom, vec![], vec![1]),
+            ],
+        )
+        .with_port("out", 0);
+        let params = SubcircuitParams {
+            source: SubcircuitSource::Embedded(Box::new(inner)),
+            inputs: Vec::new(),
+            outputs: vec!["out".into()],
+        };
+
+        // the instance comes first, its rom is appended after the one of the schematic
+        let schema = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                comp(CompType::Subcircuit(params), vec![], vec![0]),
+                comp(outer_rom, vec![], vec![1]),
+            ],
+        );
+        let prepared = schema.prepare().unwrap();
+        let flat = prepared.schema();
+        assert!(matches!(flat.comps()[0].comp_type, CompType::Fixed(7)));
+        assert_eq!(flat.comps()[1].pins_out, vec![1]);
+        assert_eq!(flat.comps().len(), 3);
+        on_every_backend(&schema, |sim| {
+            sim.step(3);
+            assert_eq!(sim.read_wire(0), Some(7));
+            assert_eq!(sim.read_wire(1), Some(1));
+            assert_eq!(sim.dump_memory(1), Some(vec![1, 2]));
+            assert_eq!(sim.dump_memory(2), Some(vec![3, 4]));
+        });
+    }
+}
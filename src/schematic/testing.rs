@@ -0,0 +1,241 @@
+/**
+ * Helpers to build schematics in the tests
+ */
+use crate::circuit::*;
+use crate::math::Vec3i;
+use crate::schematic::*;
+use crate::simulator::{Backend, Simulator};
+use std::fmt;
+
+// model at the origin
+pub fn attr() -> ModelAttr {
+    ModelAttr {
+        position: Vec3i::new(0, 0, 0),
+        mesh_index: 0,
+    }
+}
+
+// empty model, the tests never render
+pub fn model() -> Model {
+    Model {
+        indexes: Vec::new(),
+        positions: Vec::new(),
+        normals: Vec::new(),
+    }
+}
+
+// wire on the given channel
+pub fn wire(channel: Channel) -> SchemaWire {
+    SchemaWire {
+        channel,
+        resolution: None,
+        model: attr(),
+    }
+}
+
+// component reading and writing the given wires
+pub fn comp(comp_type: CompType, pins_in: Vec<Index>, pins_out: Vec<Index>) -> SchemaComp {
+    SchemaComp {
+        comp_type,
+        pins_in,
+        pins_out,
+        delay: None,
+        model: attr(),
+    }
+}
+
+// gate with the default overflow mode
+pub fn gate(op: Operator, pins_in: Vec<Index>, pins_out: Vec<Index>) -> SchemaComp {
+    comp(
+        CompType::Gate(op, OverflowMode::default()),
+        pins_in,
+        pins_out,
+    )
+}
+
+// schematic with a single empty model
+pub fn schema(wires: Vec<SchemaWire>, comps: Vec<SchemaComp>) -> Schema {
+    Schema::new(wires, comps, vec![model()])
+}
+
+// xorshift generator, the tests stay reproducible
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    pub fn data(&mut self) -> Data {
+        self.next_u64() & DataWidth::default().mask()
+    }
+}
+
+// operators added after the original ones
+const EXTENDED: [Operator; 13] = [
+    Operator::Add,
+    Operator::Mul,
+    Operator::Xor,
+    Operator::Xnor,
+    Operator::Not,
+    Operator::Sub,
+    Operator::Div,
+    Operator::Mod,
+    Operator::Shl,
+    Operator::Shr,
+    Operator::Eq,
+    Operator::Lt,
+    Operator::Gt,
+];
+
+// component of the original circuit plugin
+fn basic_type(rng: &mut Rng) -> CompType {
+    let default = OverflowMode::default();
+    match rng.below(9) {
+        0 => CompType::Gate(Operator::Or, default),
+        1 => CompType::Gate(Operator::And, default),
+        2 => CompType::Gate(Operator::Nor, default),
+        3 => CompType::Gate(Operator::Nand, default),
+        4 => CompType::Gate(Operator::Min, default),
+        5 => CompType::Gate(Operator::Max, default),
+        6 => CompType::Mux,
+        7 => CompType::Demux(rng.data()),
+        _ if rng.below(2) == 0 => CompType::Fixed(rng.data()),
+        _ => CompType::Input,
+    }
+}
+
+// component including the extended operators and the sequential components
+fn extended_type(rng: &mut Rng) -> CompType {
+    if rng.below(5) == 0 {
+        match rng.below(4) {
+            0 => CompType::Register,
+            1 => CompType::Latch,
+            2 => CompType::Toggle,
+            _ => CompType::Clock(ClockParams {
+                high: rng.below(4) as u32,
+                low: rng.below(4) as u32,
+                phase: rng.below(8) as u32,
+            }),
+        }
+    } else if rng.below(2) == 0 {
+        let overflow = match rng.below(2) {
+            0 => OverflowMode::Saturating,
+            _ => OverflowMode::Wrapping,
+        };
+        CompType::Gate(EXTENDED[rng.below(13) as usize], overflow)
+    } else {
+        basic_type(rng)
+    }
+}
+
+// random wires and components, with loops and several drivers per wire,
+// `extended` adds the operators and components added after the original ones
+pub fn random_parts(seed: u64, extended: bool) -> (Vec<SchemaWire>, Vec<SchemaComp>) {
+    let mut rng = Rng(seed);
+    let nb_wires = 5 + rng.below(30);
+    let wires = (0..nb_wires)
+        .map(|_| wire(rng.below(16) as Channel))
+        .collect();
+    let nb_comps = 3 + rng.below(30);
+    let comps = (0..nb_comps)
+        .map(|_| {
+            let comp_type = match extended {
+                true => extended_type(&mut rng),
+                false => basic_type(&mut rng),
+            };
+            let nb_in = match comp_type {
+                CompType::Fixed(_) | CompType::Input | CompType::Clock(_) => 0,
+                _ => 1 + rng.below(3),
+            };
+            let nb_out = 1 + rng.below(3);
+            let pins_in = (0..nb_in).map(|_| rng.below(nb_wires) as Index).collect();
+            let pins_out = (0..nb_out).map(|_| rng.below(nb_wires) as Index).collect();
+            comp(comp_type, pins_in, pins_out)
+        })
+        .collect();
+    (wires, comps)
+}
+
+// random schematic, see `random_parts`
+pub fn random_schema(seed: u64, extended: bool) -> Schema {
+    let (wires, comps) = random_parts(seed, extended);
+    schema(wires, comps)
+}
+
+// random data for every channel of the input device
+pub fn random_input(seed: u64) -> [Data; NB_CHANNELS] {
+    let mut rng = Rng(seed * 7 + 1);
+    std::array::from_fn(|_| rng.data())
+}
+
+// both implementations of the simulator
+pub const BACKENDS: [Backend; 2] = [Backend::Ecs, Backend::Netlist];
+
+// run the test on the circuit of the schematic with every backend,
+// they must all give the same result which is returned
+pub fn on_every_backend<T: PartialEq + fmt::Debug>(
+    schema: &Schema,
+    mut test: impl FnMut(&mut Simulator) -> T,
+) -> T {
+    let mut results = Vec::<(Backend, T)>::new();
+    for backend in BACKENDS {
+        let mut sim = Simulator::with_backend(schema.clone(), backend).expect("valid schematic");
+        results.push((backend, test(&mut sim)));
+    }
+    let (first, result) = results.remove(0);
+    for (backend, other) in results {
+        assert_eq!(other, result, "{:?} differs from {:?}", backend, first);
+    }
+    result
+}
+
+// set every channel of the input device
+pub fn write_inputs(sim: &mut Simulator, input: &[Data; NB_CHANNELS]) {
+    for (channel, data) in input.iter().enumerate() {
+        sim.write_input(channel as Channel, *data);
+    }
+}
+
+// data of every wire ordered by index
+pub fn read_wires(sim: &Simulator) -> Vec<Data> {
+    (0..sim.wire_count() as Index)
+        .map(|i| sim.read_wire(i).unwrap_or_default())
+        .collect()
+}
+
+// file in the temporary directory, unique to the test process,
+// the file is removed when the path is dropped
+pub struct TempPath(String);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl std::ops::Deref for TempPath {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<std::path::Path> for TempPath {
+    fn as_ref(&self) -> &std::path::Path {
+        self.0.as_ref()
+    }
+}
+
+pub fn temp_path(name: &str) -> TempPath {
+    let file = format!("logic-circuit-{}-{}", std::process::id(), name);
+    let path = std::env::temp_dir().join(file);
+    TempPath(path.to_string_lossy().into_owned())
+}
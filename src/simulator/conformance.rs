@@ -0,0 +1,131 @@
+use crate::circuit::*;
+use crate::schematic::testing::*;
+use crate::schematic::*;
+
+// expected output of an operator for the given operands
+struct Conformance {
+    operator: Operator,
+    overflow: OverflowMode,
+    operands: &'static [Data],
+    expected: Data,
+}
+
+const fn case(
+    operator: Operator,
+    overflow: OverflowMode,
+    operands: &'static [Data],
+    expected: Data,
+) -> Conformance {
+    Conformance {
+        operator,
+        overflow,
+        operands,
+        expected,
+    }
+}
+
+const MASK16: Data = 0xffff;
+const WRAP: OverflowMode = OverflowMode::Wrapping;
+const SAT: OverflowMode = OverflowMode::Saturating;
+
+// reference table every backend running gates has to agree with, at the default width
+const CONFORMANCE: &[Conformance] = &[
+    case(Operator::Or, WRAP, &[], 0),
+    case(Operator::Or, WRAP, &[0b0101, 0b0011], 0b0111),
+    case(Operator::And, WRAP, &[], MASK16),
+    case(Operator::And, WRAP, &[0b0101, 0b0011], 0b0001),
+    case(Operator::And, WRAP, &[MASK16], MASK16),
+    case(Operator::Nor, WRAP, &[], MASK16),
+    case(Operator::Nor, WRAP, &[0b0101, 0b0011], MASK16 ^ 0b0111),
+    case(Operator::Nand, WRAP, &[], 0),
+    case(Operator::Nand, WRAP, &[0b0101, 0b0011], MASK16 ^ 0b0001),
+    case(Operator::Add, WRAP, &[], 0),
+    case(Operator::Add, WRAP, &[1, 2, 3], 6),
+    case(Operator::Add, WRAP, &[MASK16, 2], 1),
+    case(Operator::Add, SAT, &[MASK16, 2], MASK16),
+    case(Operator::Mul, WRAP, &[], 1),
+    case(Operator::Mul, WRAP, &[3, 5], 15),
+    case(Operator::Mul, WRAP, &[MASK16, 2], MASK16 - 1),
+    case(Operator::Mul, SAT, &[MASK16, 2], MASK16),
+    case(Operator::Mul, WRAP, &[7, 0], 0),
+    case(Operator::Min, WRAP, &[], MASK16),
+    case(Operator::Min, WRAP, &[7, 3, 9], 3),
+    case(Operator::Max, WRAP, &[], 0),
+    case(Operator::Max, WRAP, &[7, 3, 9], 9),
+    case(Operator::Xor, WRAP, &[], 0),
+    case(Operator::Xor, WRAP, &[0b0101, 0b0011], 0b0110),
+    case(Operator::Xnor, WRAP, &[], MASK16),
+    case(Operator::Xnor, WRAP, &[0b0101, 0b0011], MASK16 ^ 0b0110),
+    case(Operator::Not, WRAP, &[], MASK16),
+    case(Operator::Not, WRAP, &[0b0101], MASK16 ^ 0b0101),
+    case(Operator::Not, WRAP, &[0b0101, MASK16], MASK16 ^ 0b0101),
+    case(Operator::Sub, WRAP, &[], 0),
+    case(Operator::Sub, WRAP, &[10, 3, 2], 5),
+    case(Operator::Sub, WRAP, &[1, 2], MASK16),
+    case(Operator::Sub, SAT, &[1, 2], 0),
+    case(Operator::Div, WRAP, &[], 0),
+    case(Operator::Div, WRAP, &[100, 7], 14),
+    case(Operator::Div, WRAP, &[100, 0], MASK16),
+    case(Operator::Mod, WRAP, &[100, 7], 2),
+    case(Operator::Mod, WRAP, &[100, 0], 100),
+    case(Operator::Shl, WRAP, &[1, 3], 8),
+    case(Operator::Shl, WRAP, &[1, 16], 0),
+    case(Operator::Shr, WRAP, &[8, 3], 1),
+    case(Operator::Shr, WRAP, &[MASK16, 16], 0),
+    case(Operator::Eq, WRAP, &[], MASK16),
+    case(Operator::Eq, WRAP, &[4, 4, 4], MASK16),
+    case(Operator::Eq, WRAP, &[4, 4, 5], 0),
+    case(Operator::Lt, WRAP, &[1, 2, 3], MASK16),
+    case(Operator::Lt, WRAP, &[1, 1], 0),
+    case(Operator::Gt, WRAP, &[3, 2, 1], MASK16),
+    case(Operator::Gt, WRAP, &[1, 2], 0),
+];
+
+// run every case of the operator conformance table on every backend,
+// return the cases on which they disagree with their output
+fn check_operators() -> Vec<(usize, Option<Data>)> {
+    CONFORMANCE
+        .iter()
+        .enumerate()
+        .map(|(case, conf)| (case, run_gate(conf)))
+        .filter(|(case, output)| *output != Some(CONFORMANCE[*case].expected))
+        .collect()
+}
+
+// each operand is a fixed value on its own channel so the order is preserved,
+// the result is read on the last wire
+fn run_gate(conf: &Conformance) -> Option<Data> {
+    let nb_operands = conf.operands.len();
+    let wires = (0..=nb_operands)
+        .map(|i| wire((i % NB_CHANNELS) as Channel))
+        .collect();
+    let mut comps: Vec<SchemaComp> = conf
+        .operands
+        .iter()
+        .enumerate()
+        .map(|(i, value)| comp(CompType::Fixed(*value), vec![], vec![i as Index]))
+        .collect();
+    comps.push(comp(
+        CompType::Gate(conf.operator, conf.overflow),
+        (0..nb_operands as Index).collect(),
+        vec![nb_operands as Index],
+    ));
+
+    // one tick to drive the operands, one tick for the gate
+    on_every_backend(&schema(wires, comps), |sim| {
+        sim.step(2);
+        sim.read_wire(nb_operands as Index)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_conform() {
+        assert!(!CONFORMANCE.is_empty());
+        let mismatches = check_operators();
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+    }
+}
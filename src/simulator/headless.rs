@@ -0,0 +1,425 @@
+use crate::circuit::*;
+use crate::netlist::{self, Netlist};
+use crate::schematic::*;
+use crate::snapshot::{self, History, Snapshot, SnapshotError};
+use crate::trace::{
+    BreakpointHit, Breakpoints, Condition, CycleDetector, TraceRecorder, WireCycle,
+};
+use bevy::{ecs::system::CommandQueue, input::InputPlugin, prelude::*};
+use std::{error, io, path};
+
+// implementation used to run the circuit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    // entities and systems of the circuit plugin
+    #[default]
+    Ecs,
+    // compiled netlist running in a tight loop
+    Netlist,
+}
+
+// the actual circuit behind the simulator
+enum Engine {
+    Ecs(Box<App>),
+    Netlist(Box<Netlist>),
+}
+
+/* Headless Simulator: run a schematic on a minimal app without window or GPU */
+pub struct Simulator {
+    engine: Engine,
+}
+
+impl Simulator {
+    // build the circuit of a schematic, the schematic is verified first
+    pub fn new(schema: Schema) -> Result<Self, Vec<Error>> {
+        Self::with_backend(schema, Backend::Ecs)
+    }
+
+    // build the circuit of a schematic with the given backend
+    pub fn with_backend(schema: Schema, backend: Backend) -> Result<Self, Vec<Error>> {
+        let engine = match backend {
+            Backend::Ecs => Engine::Ecs(Box::new(build_app(schema)?)),
+            Backend::Netlist => Engine::Netlist(Box::new(netlist::compile(&schema)?)),
+        };
+        Ok(Self { engine })
+    }
+
+    // run the circuit for the given amount of ticks, regardless of frame timing,
+    // stop early when a breakpoint fires, return the number of ticks executed
+    pub fn step(&mut self, n: u64) -> u64 {
+        match &mut self.engine {
+            Engine::Ecs(app) => {
+                for i in 0..n {
+                    app.world.run_schedule(CircuitTick);
+                    if app
+                        .world
+                        .get_resource::<Breakpoints>()
+                        .is_some_and(|breakpoints| breakpoints.is_hit())
+                    {
+                        return i + 1;
+                    }
+                }
+                n
+            }
+            Engine::Netlist(net) => net.step(n),
+        }
+    }
+
+    // number of ticks executed since the circuit was built
+    pub fn ticks(&self) -> u64 {
+        match &self.engine {
+            Engine::Ecs(app) => app.world.resource::<TickCount>().0,
+            Engine::Netlist(net) => net.ticks(),
+        }
+    }
+
+    // number of bits carried by the wires
+    pub fn width(&self) -> DataWidth {
+        match &self.engine {
+            Engine::Ecs(app) => *app.world.resource::<DataWidth>(),
+            Engine::Netlist(net) => net.width(),
+        }
+    }
+
+    // number of wires in the circuit
+    pub fn wire_count(&self) -> usize {
+        match &self.engine {
+            Engine::Ecs(app) => app.world.resource::<SchemaWires>().0.len(),
+            Engine::Netlist(net) => net.wire_count(),
+        }
+    }
+
+    // channel of the wire
+    pub fn wire_channel(&self, index: Index) -> Option<Channel> {
+        match &self.engine {
+            Engine::Ecs(app) => {
+                let wires = app.world.resource::<SchemaWires>();
+                let entity = *wires.0.get(index as usize)?;
+                app.world.get::<PinChannel>(entity).map(|channel| channel.0)
+            }
+            Engine::Netlist(net) => net.channel(index as usize),
+        }
+    }
+
+    // value driven on the wire by the last tick
+    pub fn read_wire(&self, index: Index) -> Option<Data> {
+        match &self.engine {
+            Engine::Ecs(app) => {
+                let wires = app.world.resource::<SchemaWires>();
+                let entity = *wires.0.get(index as usize)?;
+                app.world.get::<DataNext>(entity).map(|pin| pin.0)
+            }
+            Engine::Netlist(net) => net.read_wire(index as usize),
+        }
+    }
+
+    // dump the words of a memory component, given its index in the schematic
+    pub fn dump_memory(&self, comp: Index) -> Option<Vec<Data>> {
+        match &self.engine {
+            Engine::Ecs(app) => {
+                let comps = app.world.resource::<SchemaComps>();
+                let entity = *comps.0.get(comp as usize)?;
+                let memory = app.world.get::<CompMemory>(entity)?;
+                Some(memory.words.clone())
+            }
+            Engine::Netlist(net) => net.memory(comp as usize).map(|words| words.to_vec()),
+        }
+    }
+
+    // write the words of a memory component to a binary or hex file
+    pub fn save_memory<P: AsRef<path::Path>>(&self, comp: Index, path: P) -> io::Result<()> {
+        match self.dump_memory(comp) {
+            Some(words) => save_image(path, &words, self.width()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "not a memory")),
+        }
+    }
+
+    // what a display component shows, given its index in the schematic
+    pub fn display(&self, comp: Index) -> Option<DisplayState> {
+        match &self.engine {
+            Engine::Ecs(app) => {
+                let comps = app.world.resource::<SchemaComps>();
+                let entity = *comps.0.get(comp as usize)?;
+                app.world.get::<DisplayState>(entity).cloned()
+            }
+            Engine::Netlist(net) => net.display(comp as usize).cloned(),
+        }
+    }
+
+    // conflicts on strict wires since the last call, ordered by tick and by wire
+    pub fn take_conflicts(&mut self) -> Vec<DriverConflict> {
+        let mut conflicts: Vec<_> = match &mut self.engine {
+            Engine::Ecs(app) => app
+                .world
+                .resource_mut::<Events<DriverConflict>>()
+                .drain()
+                .collect(),
+            Engine::Netlist(net) => net.take_conflicts(),
+        };
+        conflicts.sort_by_key(|conflict| (conflict.tick, conflict.wire));
+        conflicts
+    }
+
+    // combinational loops which did not settle since the last call, ordered by tick
+    pub fn take_oscillations(&mut self) -> Vec<Oscillation> {
+        match &mut self.engine {
+            Engine::Ecs(app) => app
+                .world
+                .resource_mut::<Events<Oscillation>>()
+                .drain()
+                .collect(),
+            Engine::Netlist(net) => net.take_oscillations(),
+        }
+    }
+
+    // set the value seen by input components on the given channel
+    pub fn write_input(&mut self, channel: Channel, data: Data) {
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.resource_mut::<InputDevice>().set(channel, data),
+            Engine::Netlist(net) => net.write_input(channel, data),
+        }
+    }
+
+    // replace the backend of a bus, the port of a bus is its rank among the buses
+    // of the schematic, return false if there is no such port
+    pub fn set_bus_backend(&mut self, port: usize, backend: Box<dyn IoBackend>) -> bool {
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.resource_mut::<IOBusDevice>().set(port, backend),
+            Engine::Netlist(net) => net.set_bus_backend(port, backend),
+        }
+    }
+
+    // take a snapshot of the wires and of the state of the components
+    pub fn snapshot(&self) -> Snapshot {
+        match &self.engine {
+            Engine::Ecs(app) => snapshot::capture(&app.world),
+            Engine::Netlist(net) => net.snapshot(),
+        }
+    }
+
+    // resume from a snapshot taken on the same schematic, with any backend,
+    // nothing is changed if it does not fit the circuit
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        match &mut self.engine {
+            Engine::Ecs(app) => snapshot::restore(&mut app.world, snapshot),
+            Engine::Netlist(net) => net.restore(snapshot),
+        }
+    }
+
+    // save a snapshot of the circuit to a file
+    pub fn save_state<P: AsRef<path::Path>>(&self, path: P) -> Result<(), Box<dyn error::Error>> {
+        self.snapshot().save(path)
+    }
+
+    // resume from a snapshot saved to a file
+    pub fn load_state<P: AsRef<path::Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let snapshot = Snapshot::load(path)?;
+        Ok(self.restore(&snapshot)?)
+    }
+
+    // keep the changes of each of the next ticks to step back, only the last `capacity`
+    // ticks are kept, a capacity of zero forgets them
+    pub fn keep_history(&mut self, capacity: usize) {
+        let history = (capacity > 0).then(|| History::new(self.snapshot(), capacity));
+        match (&mut self.engine, history) {
+            (Engine::Ecs(app), Some(history)) => app.world.insert_resource(history),
+            (Engine::Ecs(app), None) => {
+                app.world.remove_resource::<History>();
+            }
+            (Engine::Netlist(net), history) => {
+                net.set_history(history);
+            }
+        }
+    }
+
+    // go back the given amount of ticks, at most the ticks kept, bytes exchanged by
+    // the buses are not taken back, return the number of ticks undone
+    pub fn step_back(&mut self, n: u64) -> u64 {
+        let history = match &mut self.engine {
+            Engine::Ecs(app) => app.world.get_resource_mut::<History>().map(Mut::into_inner),
+            Engine::Netlist(net) => net.history_mut(),
+        };
+        let Some(history) = history else {
+            return 0;
+        };
+        let undone = history.back(n);
+        let snapshot = history.current().clone();
+        self.restore(&snapshot)
+            .expect("the history is kept on the same circuit");
+        undone
+    }
+
+    // record the given wires, or every wire, on each of the next ticks,
+    // only the last `capacity` ticks are kept, a previous recording is dropped
+    pub fn start_trace(&mut self, wires: Option<&[Index]>, capacity: usize) {
+        let indexes: Vec<Index> = match wires {
+            Some(wires) => wires.to_vec(),
+            None => (0..self.wire_count() as Index).collect(),
+        };
+        let wires = indexes
+            .into_iter()
+            .filter_map(|i| Some((i, self.wire_channel(i)?)))
+            .collect();
+        let recorder = TraceRecorder::new(wires, self.width(), capacity);
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.insert_resource(recorder),
+            Engine::Netlist(net) => {
+                net.set_trace(Some(recorder));
+            }
+        }
+    }
+
+    // ticks recorded so far
+    pub fn trace(&self) -> Option<&TraceRecorder> {
+        match &self.engine {
+            Engine::Ecs(app) => app.world.get_resource::<TraceRecorder>(),
+            Engine::Netlist(net) => net.trace(),
+        }
+    }
+
+    // stop recording and return the ticks recorded
+    pub fn stop_trace(&mut self) -> Option<TraceRecorder> {
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.remove_resource::<TraceRecorder>(),
+            Engine::Netlist(net) => net.set_trace(None),
+        }
+    }
+
+    // watch the given wires, or every wire, for data repeating with a period of at most
+    // `max_period` ticks, a previous detector is dropped
+    pub fn start_cycle_detector(&mut self, wires: Option<&[Index]>, max_period: usize) {
+        let wires = match wires {
+            Some(wires) => wires.to_vec(),
+            None => (0..self.wire_count() as Index).collect(),
+        };
+        let detector = CycleDetector::new(wires, max_period);
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.insert_resource(detector),
+            Engine::Netlist(net) => {
+                net.set_cycle_detector(Some(detector));
+            }
+        }
+    }
+
+    pub fn cycle_detector(&self) -> Option<&CycleDetector> {
+        match &self.engine {
+            Engine::Ecs(app) => app.world.get_resource::<CycleDetector>(),
+            Engine::Netlist(net) => net.cycle_detector(),
+        }
+    }
+
+    // stop watching the wires and return the detector
+    pub fn stop_cycle_detector(&mut self) -> Option<CycleDetector> {
+        match &mut self.engine {
+            Engine::Ecs(app) => app.world.remove_resource::<CycleDetector>(),
+            Engine::Netlist(net) => net.set_cycle_detector(None),
+        }
+    }
+
+    // wires found cycling since the last call, nothing without a detector
+    pub fn take_cycles(&mut self) -> Vec<WireCycle> {
+        let detector = match &mut self.engine {
+            Engine::Ecs(app) => app
+                .world
+                .get_resource_mut::<CycleDetector>()
+                .map(Mut::into_inner),
+            Engine::Netlist(net) => net.cycle_detector_mut(),
+        };
+        detector.map_or_else(Vec::new, CycleDetector::take_cycles)
+    }
+
+    // check the condition after each tick, return the id of the breakpoint
+    pub fn add_breakpoint(&mut self, condition: Condition) -> usize {
+        self.breakpoints_mut().add(condition)
+    }
+
+    // return false if there is no such breakpoint
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.breakpoints_mut().remove(id)
+    }
+
+    // breakpoints which fired since the last call
+    pub fn take_hits(&mut self) -> Vec<BreakpointHit> {
+        self.breakpoints_mut().take_hits()
+    }
+
+    fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        match &mut self.engine {
+            Engine::Ecs(app) => app
+                .world
+                .get_resource_or_insert_with(Breakpoints::default)
+                .into_inner(),
+            Engine::Netlist(net) => net.breakpoints_mut(),
+        }
+    }
+
+    // give access to the underlying app to add custom plugins or systems,
+    // only available with the ECS backend
+    pub fn app_mut(&mut self) -> Option<&mut App> {
+        match &mut self.engine {
+            Engine::Ecs(app) => Some(app),
+            Engine::Netlist(_) => None,
+        }
+    }
+}
+
+// build a minimal app running the circuit, the schematic is verified first
+fn build_app(schema: Schema) -> Result<App, Vec<Error>> {
+    let prepared = schema.prepare()?;
+
+    let mut app = App::new();
+    app
+        // no window and no renderer, only the core of bevy
+        .add_plugins((MinimalPlugins, InputPlugin))
+        // add the systems that will run the circuitry
+        .add_plugins(CircuitPlugin);
+
+    // spawn the same entities as `build_circuit` but without any mesh
+    let mut queue = CommandQueue::default();
+    prepared.spawn(&mut Commands::new(&mut queue, &app.world));
+    queue.apply(&mut app.world);
+
+    app.insert_resource(schema);
+    app.finish();
+    app.cleanup();
+
+    Ok(app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+
+    #[test]
+    fn runs_schema() {
+        let schema = schema(
+            vec![wire(0), wire(0), wire(1)],
+            vec![
+                comp(CompType::Fixed(5), vec![], vec![0]),
+                gate(Operator::Nor, vec![0], vec![1]),
+                comp(CompType::Input, vec![], vec![2]),
+            ],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        sim.write_input(1, 42);
+        sim.step(1);
+        assert_eq!(sim.ticks(), 1);
+        assert_eq!(sim.wire_count(), 3);
+        assert_eq!(sim.read_wire(0), Some(5));
+        assert_eq!(sim.read_wire(1), Some(0xffff));
+        assert_eq!(sim.read_wire(2), Some(42));
+        assert_eq!(sim.read_wire(3), None);
+        sim.step(1);
+        assert_eq!(sim.read_wire(1), Some(0xffff ^ 5));
+    }
+
+    #[test]
+    fn rejects_invalid_schema() {
+        let schema = schema(vec![wire(0)], vec![comp(CompType::Input, vec![], vec![3])]);
+        assert!(Simulator::new(schema).is_err());
+    }
+}
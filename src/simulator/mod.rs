@@ -0,0 +1,8 @@
+/**
+ * Run logic circuits without window or renderer
+ */
+#[cfg(test)]
+mod conformance;
+mod headless;
+
+pub use headless::{Backend, Simulator};
@@ -0,0 +1,102 @@
+use crate::circuit::*;
+use serde::{Deserialize, Serialize};
+use std::{error, fmt, fs, mem, path};
+
+// internal state of a component, components without state have none
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CompState {
+    None,
+    Register(CompRegister),
+    Latch(CompLatch),
+    Toggle(CompToggle),
+    Memory(CompMemory),
+    Clock(CompClock),
+    Bus(CompIOBus),
+    Display(DisplayState),
+    TriState(CompTriState),
+}
+
+// a snapshot which does not fit the circuit it is restored into
+#[derive(Debug)]
+pub enum SnapshotError {
+    // number of wires expected by the circuit
+    Wires(usize),
+    // number of components expected by the circuit
+    Comps(usize),
+    // index of a component with another kind of state
+    Comp(usize),
+}
+impl error::Error for SnapshotError {}
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Wires(n) => write!(f, "Snapshot Wire Error, expected={}", n),
+            Self::Comps(n) => write!(f, "Snapshot Component Error, expected={}", n),
+            Self::Comp(n) => write!(f, "Snapshot Component State Error at {}", n),
+        }
+    }
+}
+
+/* Snapshot: data of the wires and state of the components, by index in the schematic */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub ticks: u64,
+    // data of the wires during the last tick and driven by it
+    pub prev: Vec<Data>,
+    pub next: Vec<Data>,
+    // buffer of the input device
+    pub input: [Data; NB_CHANNELS],
+    pub comps: Vec<CompState>,
+    // data on its way through delayed components
+    pub pipelines: Vec<Option<Pipeline>>,
+}
+
+impl Snapshot {
+    // check that the snapshot fits a circuit given the current state of its components
+    // and of their pipelines
+    pub fn check(
+        &self,
+        wires: usize,
+        comps: &[CompState],
+        pipelines: &[Option<Pipeline>],
+    ) -> Result<(), SnapshotError> {
+        if self.prev.len() != wires || self.next.len() != wires {
+            return Err(SnapshotError::Wires(wires));
+        }
+        if self.comps.len() != comps.len() || self.pipelines.len() != pipelines.len() {
+            return Err(SnapshotError::Comps(comps.len()));
+        }
+        let fits = |(saved, current): (&Option<Pipeline>, &Option<Pipeline>)| match (saved, current)
+        {
+            (Some(saved), Some(current)) => {
+                saved.nb_stages() == current.nb_stages()
+                    && saved.driven().len() == current.driven().len()
+            }
+            (saved, current) => saved.is_none() && current.is_none(),
+        };
+        match self
+            .comps
+            .iter()
+            .zip(comps.iter())
+            .zip(self.pipelines.iter().zip(pipelines.iter()))
+            .position(|((saved, current), pipelines)| {
+                mem::discriminant(saved) != mem::discriminant(current) || !fits(pipelines)
+            }) {
+            Some(n) => Err(SnapshotError::Comp(n)),
+            None => Ok(()),
+        }
+    }
+
+    // load a snapshot from a binary file
+    pub fn load<P: AsRef<path::Path>>(path: P) -> Result<Self, Box<dyn error::Error>> {
+        let buffer = fs::read(path)?;
+        Ok(bincode::deserialize::<Self>(&buffer)?)
+    }
+
+    // save the snapshot to a binary file
+    pub fn save<P: AsRef<path::Path>>(&self, path: P) -> Result<(), Box<dyn error::Error>> {
+        let buffer = bincode::serialize(self)?;
+        fs::write(path, buffer)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use super::*;
+use crate::circuit::*;
+use crate::schematic::{Index, SchemaComps, SchemaWires};
+use bevy::ecs::query::WorldQuery;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::VecDeque;
+
+// value changed by a tick, with the value before the tick
+#[derive(Clone, Debug)]
+enum Delta {
+    // data of a wire during the tick and driven by it
+    Wire(Index, Data, Data),
+    Input(Channel, Data),
+    Comp(Index, CompState),
+    // memories only keep the words which changed
+    Word(Index, usize, Data),
+    Pipeline(Index, Option<Pipeline>),
+}
+
+// changes of a single tick and the tick count before it
+struct Step {
+    ticks: u64,
+    deltas: Vec<Delta>,
+}
+
+/* History: changes of the circuit on the last ticks, to go back in time */
+#[derive(Resource)]
+pub struct History {
+    // oldest steps are dropped once the capacity is reached
+    capacity: usize,
+    steps: VecDeque<Step>,
+    // changes reported for the tick being recorded
+    pending: Vec<Delta>,
+    // state of the circuit after the last recorded tick
+    current: Snapshot,
+}
+
+impl History {
+    // start from the current state of the circuit, keep at most `capacity` ticks
+    pub fn new(current: Snapshot, capacity: usize) -> Self {
+        Self {
+            capacity,
+            steps: VecDeque::with_capacity(capacity.min(1 << 16)),
+            pending: Vec::new(),
+            current,
+        }
+    }
+
+    // state of the circuit after the last recorded or undone tick
+    pub fn current(&self) -> &Snapshot {
+        &self.current
+    }
+
+    // data of a wire which may have changed during the tick
+    pub fn wire(&mut self, i: Index, prev: Data, next: Data) {
+        let current = &mut self.current;
+        let (old_prev, old_next) = (current.prev[i as usize], current.next[i as usize]);
+        if old_prev != prev || old_next != next {
+            self.pending.push(Delta::Wire(i, old_prev, old_next));
+            current.prev[i as usize] = prev;
+            current.next[i as usize] = next;
+        }
+    }
+
+    pub fn input(&mut self, channel: Channel, data: Data) {
+        let old = std::mem::replace(&mut self.current.input[channel as usize], data);
+        if old != data {
+            self.pending.push(Delta::Input(channel, old));
+        }
+    }
+
+    // state of a component which may have changed, memories report their words instead
+    pub fn comp(&mut self, i: Index, state: CompState) {
+        if self.current.comps[i as usize] != state {
+            let old = std::mem::replace(&mut self.current.comps[i as usize], state);
+            self.pending.push(Delta::Comp(i, old));
+        }
+    }
+
+    // word of a memory which may have been written during the tick
+    pub fn word(&mut self, i: Index, address: usize, word: Data) {
+        let CompState::Memory(memory) = &mut self.current.comps[i as usize] else {
+            return;
+        };
+        if let Some(old) = memory.words.get_mut(address) {
+            if *old != word {
+                self.pending.push(Delta::Word(i, address, *old));
+                *old = word;
+            }
+        }
+    }
+
+    pub fn pipeline(&mut self, i: Index, pipeline: &Pipeline) {
+        let current = &mut self.current.pipelines[i as usize];
+        if current.as_ref() != Some(pipeline) {
+            let old = current.replace(pipeline.clone());
+            self.pending.push(Delta::Pipeline(i, old));
+        }
+    }
+
+    // keep the changes reported since the last tick recorded
+    pub fn commit(&mut self, ticks: u64) {
+        let step = Step {
+            ticks: std::mem::replace(&mut self.current.ticks, ticks),
+            deltas: std::mem::take(&mut self.pending),
+        };
+        if self.capacity == 0 {
+            return;
+        }
+        if self.steps.len() == self.capacity {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    // undo the last ticks, at most the recorded ones, return the number of ticks undone
+    pub fn back(&mut self, n: u64) -> u64 {
+        let mut undone = 0;
+        while undone < n {
+            let Some(step) = self.steps.pop_back() else {
+                break;
+            };
+            let current = &mut self.current;
+            current.ticks = step.ticks;
+            for delta in step.deltas.into_iter().rev() {
+                match delta {
+                    Delta::Wire(i, prev, next) => {
+                        current.prev[i as usize] = prev;
+                        current.next[i as usize] = next;
+                    }
+                    Delta::Input(c, data) => current.input[c as usize] = data,
+                    Delta::Comp(i, state) => current.comps[i as usize] = state,
+                    Delta::Pipeline(i, pipeline) => current.pipelines[i as usize] = pipeline,
+                    Delta::Word(i, address, word) => {
+                        if let CompState::Memory(memory) = &mut current.comps[i as usize] {
+                            memory.words[address] = word;
+                        }
+                    }
+                }
+            }
+            undone += 1;
+        }
+        undone
+    }
+}
+
+// go back one tick when F7 is pressed, the circuit is paused to stay there
+pub fn sys_step_back(world: &mut World) {
+    let pressed = world
+        .get_resource::<Input<KeyCode>>()
+        .is_some_and(|keys| keys.just_pressed(KeyCode::F7));
+    if !pressed {
+        return;
+    }
+    let mut history = world.resource_mut::<History>();
+    history.back(1);
+    let snapshot = history.current().clone();
+    restore(world, &snapshot).expect("the history is kept on the same circuit");
+    world
+        .resource_mut::<NextState<SimState>>()
+        .set(SimState::Paused);
+}
+
+// wires which data may have changed during the tick
+type WireChanged = Or<(Changed<DataPrev>, Changed<DataNext>)>;
+// components which internal state may have changed, apart from memories
+type StateChanged = Or<(
+    Changed<CompRegister>,
+    Changed<CompLatch>,
+    Changed<CompToggle>,
+    Changed<CompTriState>,
+    Changed<CompClock>,
+    Changed<CompIOBus>,
+    Changed<DisplayState>,
+)>;
+
+// internal state of the components apart from memories
+type AnyState = AnyOf<(
+    &'static CompRegister,
+    &'static CompLatch,
+    &'static CompToggle,
+    &'static CompTriState,
+    &'static CompClock,
+    &'static CompIOBus,
+    &'static DisplayState,
+)>;
+
+fn any_state(state: <AnyState as WorldQuery>::Item<'_>) -> CompState {
+    match state {
+        (Some(register), ..) => CompState::Register(*register),
+        (_, Some(latch), ..) => CompState::Latch(*latch),
+        (_, _, Some(toggle), ..) => CompState::Toggle(*toggle),
+        (_, _, _, Some(tristate), ..) => CompState::TriState(*tristate),
+        (_, _, _, _, Some(clock), ..) => CompState::Clock(*clock),
+        (_, _, _, _, _, Some(bus), _) => CompState::Bus(*bus),
+        (.., Some(display)) => CompState::Display(display.clone()),
+        _ => CompState::None,
+    }
+}
+
+// keep the changes of the tick, only the wires and components flagged as changed
+// are compared, and the word at the address of the memories evaluated
+#[allow(clippy::too_many_arguments)]
+pub fn sys_history(
+    mut history: ResMut<History>,
+    count: Res<TickCount>,
+    device: Res<InputDevice>,
+    wires: Res<SchemaWires>,
+    comps: Res<SchemaComps>,
+    mut indices: Local<HashMap<Entity, Index>>,
+    wire_query: Query<(Entity, &DataPrev, &DataNext), WireChanged>,
+    comp_query: Query<(Entity, AnyState), StateChanged>,
+    memory_query: Query<(Entity, &CompMemory, &PinsIn), Changed<CompMemory>>,
+    pipeline_query: Query<(Entity, &Pipeline), Changed<Pipeline>>,
+    prev_query: Query<(&PinChannel, &DataPrev)>,
+) {
+    // index in the schematic of the wires and components
+    if wires.is_changed() || comps.is_changed() {
+        indices.clear();
+        indices.extend(wires.0.iter().enumerate().map(|(i, e)| (*e, i as Index)));
+        indices.extend(comps.0.iter().enumerate().map(|(i, e)| (*e, i as Index)));
+    }
+
+    for (entity, prev, next) in wire_query.iter() {
+        if let Some(i) = indices.get(&entity) {
+            history.wire(*i, prev.0, next.0);
+        }
+    }
+    for channel in 0..NB_CHANNELS {
+        history.input(channel as Channel, device.get(channel as Channel));
+    }
+    for (entity, state) in comp_query.iter() {
+        if let Some(i) = indices.get(&entity) {
+            history.comp(*i, any_state(state));
+        }
+    }
+    for (entity, memory, pins_in) in memory_query.iter() {
+        let address = CompMemory::address(&read_by_channel(pins_in, &prev_query));
+        if let (Some(i), Some(word)) = (indices.get(&entity), memory.words.get(address)) {
+            history.word(*i, address, *word);
+        }
+    }
+    for (entity, pipeline) in pipeline_query.iter() {
+        if let Some(i) = indices.get(&entity) {
+            history.pipeline(*i, pipeline);
+        }
+    }
+    history.commit(count.0);
+}
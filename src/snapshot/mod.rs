@@ -0,0 +1,150 @@
+/**
+ * Save the state of a running circuit to resume from it later or to go back in time
+ */
+mod base;
+mod history;
+mod world;
+
+pub use base::{CompState, Snapshot, SnapshotError};
+pub use history::{sys_history, sys_step_back, History};
+pub use world::{
+    capture, load_state, restore, save_state, start_history, HistoryCapacity, StateLoad, StateSave,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{SimState, TickCount};
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+    use crate::simulator::Simulator;
+    use bevy::input::{keyboard::KeyboardInput, ButtonState};
+    use bevy::prelude::*;
+
+    #[test]
+    fn resumes_on_every_backend() {
+        for seed in 1..60 {
+            let schema = random_schema(seed, true);
+            for from in BACKENDS {
+                let mut sim = Simulator::with_backend(schema.clone(), from).unwrap();
+                write_inputs(&mut sim, &random_input(seed));
+                sim.step(15);
+                let snapshot = sim.snapshot();
+                sim.step(10);
+                let expected = read_wires(&sim);
+                for to in BACKENDS {
+                    let mut resumed = Simulator::with_backend(schema.clone(), to).unwrap();
+                    resumed.restore(&snapshot).unwrap();
+                    assert_eq!(resumed.ticks(), 15);
+                    resumed.step(10);
+                    let wires = read_wires(&resumed);
+                    assert_eq!(wires, expected, "seed {} {:?} to {:?}", seed, from, to);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn steps_back() {
+        for seed in 1..40 {
+            let schema = random_schema(seed, true);
+            on_every_backend(&schema, |sim| {
+                write_inputs(sim, &random_input(seed));
+                sim.step(4);
+                sim.keep_history(8);
+                let snapshots: Vec<_> = (0..16)
+                    .map(|_| {
+                        sim.step(1);
+                        sim.snapshot()
+                    })
+                    .collect();
+                assert_eq!(sim.step_back(3), 3);
+                assert_eq!(sim.snapshot(), snapshots[12]);
+                sim.step(3);
+                assert_eq!(sim.snapshot(), snapshots[15]);
+
+                // only the last ticks are kept
+                assert_eq!(sim.step_back(100), 8);
+                assert_eq!(sim.ticks(), 12);
+                assert_eq!(sim.snapshot(), snapshots[7]);
+                assert_eq!(sim.step_back(1), 0);
+                sim.keep_history(0);
+                sim.step(2);
+                assert_eq!(sim.step_back(1), 0);
+                read_wires(sim)
+            });
+        }
+    }
+
+    #[test]
+    fn steps_back_with_key() {
+        let mut sim = Simulator::new(random_schema(5, true)).unwrap();
+        sim.keep_history(4);
+        sim.step(3);
+        let app = sim.app_mut().unwrap();
+        app.world
+            .resource_mut::<NextState<SimState>>()
+            .set(SimState::Paused);
+        app.update();
+        let ticks = app.world.resource::<TickCount>().0;
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::F7),
+            state: ButtonState::Pressed,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks - 1);
+        // the key is only taken into account when it is pressed
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, ticks - 1);
+    }
+
+    #[test]
+    fn saved_to_file() {
+        let schema = schema(
+            vec![wire(0), wire(1)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                comp(CompType::Toggle, vec![0], vec![1]),
+            ],
+        );
+        let path = temp_path("state.bin");
+        on_every_backend(&schema, |sim| {
+            sim.write_input(0, 1);
+            sim.step(3);
+            sim.save_state(&path).unwrap();
+            let saved = sim.snapshot();
+            sim.write_input(0, 0);
+            sim.step(3);
+            sim.load_state(&path).unwrap();
+            assert_eq!(sim.snapshot(), saved);
+            assert_eq!(sim.read_wire(1), Some(sim.width().mask()));
+            saved
+        });
+    }
+
+    #[test]
+    fn rejects_other_circuit() {
+        let toggle = schema(
+            vec![wire(0)],
+            vec![comp(CompType::Toggle, vec![0], vec![0])],
+        );
+        let latch = schema(vec![wire(0)], vec![comp(CompType::Latch, vec![0], vec![0])]);
+        let wider = schema(
+            vec![wire(0), wire(0)],
+            vec![comp(CompType::Toggle, vec![0], vec![0])],
+        );
+        on_every_backend(&toggle, |sim| {
+            sim.step(1);
+            let snapshot = sim.snapshot();
+            let mut other = Simulator::new(latch.clone()).unwrap();
+            let error = other.restore(&snapshot).unwrap_err();
+            assert!(matches!(error, SnapshotError::Comp(0)));
+            let mut other = Simulator::new(wider.clone()).unwrap();
+            let error = other.restore(&snapshot).unwrap_err();
+            assert!(matches!(error, SnapshotError::Wires(2)));
+            assert_eq!(other.ticks(), 0);
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use super::*;
+use crate::circuit::*;
+use crate::schematic::{SchemaComps, SchemaWires};
+use bevy::app::AppExit;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::world::EntityWorldMut;
+use bevy::prelude::*;
+use std::path;
+
+// file the circuit resumes from once it is built
+#[derive(Resource)]
+pub struct StateLoad(pub path::PathBuf);
+
+// file the state of the circuit is saved to when the app exits
+#[derive(Resource)]
+pub struct StateSave(pub path::PathBuf);
+
+// keep the changes of the given amount of ticks once the circuit is built
+#[derive(Resource)]
+pub struct HistoryCapacity(pub usize);
+
+// take a snapshot of the circuit spawned in the world
+pub fn capture(world: &World) -> Snapshot {
+    let wires = &world.resource::<SchemaWires>().0;
+    let comps = &world.resource::<SchemaComps>().0;
+    let device = world.resource::<InputDevice>();
+    Snapshot {
+        ticks: world.resource::<TickCount>().0,
+        prev: wires
+            .iter()
+            .map(|e| world.get::<DataPrev>(*e).map_or(0, |data| data.0))
+            .collect(),
+        next: wires
+            .iter()
+            .map(|e| world.get::<DataNext>(*e).map_or(0, |data| data.0))
+            .collect(),
+        input: std::array::from_fn(|c| device.get(c as Channel)),
+        comps: comps.iter().map(|e| comp_state(world.entity(*e))).collect(),
+        pipelines: comps
+            .iter()
+            .map(|e| world.get::<Pipeline>(*e).cloned())
+            .collect(),
+    }
+}
+
+// resume from the snapshot saved to a file, nothing is loaded for an invalid circuit
+pub fn load_state(world: &mut World) {
+    if !world.contains_resource::<SchemaWires>() {
+        return;
+    }
+    let path = world.resource::<StateLoad>().0.clone();
+    let loaded = Snapshot::load(&path).and_then(|snapshot| Ok(restore(world, &snapshot)?));
+    if let Err(e) = loaded {
+        error!("Cannot resume from {}: {}", path.display(), e);
+    }
+}
+
+// save a snapshot of the circuit when the app exits
+pub fn save_state(world: &mut World, mut exit: Local<ManualEventReader<AppExit>>) {
+    let exited = exit
+        .read(world.resource::<Events<AppExit>>())
+        .next()
+        .is_some();
+    if !exited || !world.contains_resource::<SchemaWires>() {
+        return;
+    }
+    let path = &world.resource::<StateSave>().0;
+    if let Err(e) = capture(world).save(path) {
+        error!("Cannot save {}: {}", path.display(), e);
+    }
+}
+
+// start keeping the changes of each tick from the current state of the circuit
+pub fn start_history(world: &mut World) {
+    if !world.contains_resource::<SchemaWires>() {
+        return;
+    }
+    let capacity = world.resource::<HistoryCapacity>().0;
+    let history = History::new(capture(world), capacity);
+    world.insert_resource(history);
+}
+
+// resume the circuit spawned in the world from a snapshot,
+// nothing is changed if the snapshot does not fit the circuit
+pub fn restore(world: &mut World, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+    let wires = world.resource::<SchemaWires>().0.clone();
+    let comps = world.resource::<SchemaComps>().0.clone();
+    let current: Vec<_> = comps.iter().map(|e| comp_state(world.entity(*e))).collect();
+    let pipelines: Vec<_> = comps
+        .iter()
+        .map(|e| world.get::<Pipeline>(*e).cloned())
+        .collect();
+    snapshot.check(wires.len(), &current, &pipelines)?;
+
+    world.resource_mut::<TickCount>().0 = snapshot.ticks;
+    let mut device = world.resource_mut::<InputDevice>();
+    for (channel, data) in snapshot.input.iter().enumerate() {
+        device.set(channel as Channel, *data);
+    }
+    for (i, entity) in wires.iter().enumerate() {
+        let mut wire = world.entity_mut(*entity);
+        wire.insert((DataPrev(snapshot.prev[i]), DataNext(snapshot.next[i])));
+    }
+    for (i, entity) in comps.iter().enumerate() {
+        let mut comp = world.entity_mut(*entity);
+        set_comp_state(&mut comp, &snapshot.comps[i]);
+        if let Some(pipeline) = &snapshot.pipelines[i] {
+            comp.insert(pipeline.clone());
+        }
+    }
+
+    // the data driven before the snapshot is stale, so every component is
+    // evaluated again and every wire resolved again on the next tick
+    world.resource_mut::<DirtyAll>().0 = true;
+    Ok(())
+}
+
+// internal state of the component entity
+fn comp_state(entity: EntityRef) -> CompState {
+    if let Some(register) = entity.get::<CompRegister>() {
+        CompState::Register(*register)
+    } else if let Some(latch) = entity.get::<CompLatch>() {
+        CompState::Latch(*latch)
+    } else if let Some(toggle) = entity.get::<CompToggle>() {
+        CompState::Toggle(*toggle)
+    } else if let Some(tristate) = entity.get::<CompTriState>() {
+        CompState::TriState(*tristate)
+    } else if let Some(memory) = entity.get::<CompMemory>() {
+        CompState::Memory(memory.clone())
+    } else if let Some(clock) = entity.get::<CompClock>() {
+        CompState::Clock(*clock)
+    } else if let Some(bus) = entity.get::<CompIOBus>() {
+        CompState::Bus(*bus)
+    } else if let Some(display) = entity.get::<DisplayState>() {
+        CompState::Display(display.clone())
+    } else {
+        CompState::None
+    }
+}
+
+// replace the internal state of the component entity
+fn set_comp_state(entity: &mut EntityWorldMut, state: &CompState) {
+    match state {
+        CompState::None => {}
+        CompState::Register(register) => {
+            entity.insert(*register);
+        }
+        CompState::Latch(latch) => {
+            entity.insert(*latch);
+        }
+        CompState::Toggle(toggle) => {
+            entity.insert(*toggle);
+        }
+        CompState::TriState(tristate) => {
+            entity.insert(*tristate);
+        }
+        CompState::Memory(memory) => {
+            entity.insert(memory.clone());
+        }
+        CompState::Clock(clock) => {
+            entity.insert(*clock);
+        }
+        CompState::Bus(bus) => {
+            entity.insert(*bus);
+        }
+        CompState::Display(display) => {
+            entity.insert(display.clone());
+        }
+    }
+}
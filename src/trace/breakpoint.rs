@@ -0,0 +1,204 @@
+use crate::circuit::*;
+use crate::schematic::{Index, SchemaComps, SchemaWires};
+use bevy::prelude::*;
+
+// condition checked at the end of every tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    // the wire holds the value
+    WireEquals(Index, Data),
+    // the value of the wire changed on the tick
+    WireChanged(Index),
+    // an output wire of the component is not null
+    CompOutput(Index),
+}
+
+// a condition fired, sent as an event and kept until taken
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub id: usize,
+    pub condition: Condition,
+    pub tick: u64,
+}
+
+/* Breakpoints: conditions pausing the circuit when they fire */
+#[derive(Default, Resource)]
+pub struct Breakpoints {
+    // removed breakpoints leave a hole so that the ids stay valid
+    conditions: Vec<Option<Condition>>,
+    hits: Vec<BreakpointHit>,
+    // a condition fired on the last tick checked
+    hit: bool,
+}
+
+impl Breakpoints {
+    // add a condition, return its id
+    pub fn add(&mut self, condition: Condition) -> usize {
+        self.conditions.push(Some(condition));
+        self.conditions.len() - 1
+    }
+
+    // remove a condition, return false if there is no such breakpoint
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.conditions
+            .get_mut(id)
+            .and_then(|slot| slot.take())
+            .is_some()
+    }
+
+    // whether a condition fired on the last tick checked
+    pub fn is_hit(&self) -> bool {
+        self.hit
+    }
+
+    // conditions which fired since the last call
+    pub fn take_hits(&mut self) -> Vec<BreakpointHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    // check every condition at the end of a tick, `wire` gives the data of a wire on the
+    // previous and on this tick and `comp` the data of the output wires of a component,
+    // return the conditions which fired
+    pub fn check<I: IntoIterator<Item = Data>>(
+        &mut self,
+        tick: u64,
+        wire: impl Fn(Index) -> Option<(Data, Data)>,
+        comp: impl Fn(Index) -> Option<I>,
+    ) -> &[BreakpointHit] {
+        let first = self.hits.len();
+        for (id, condition) in self.conditions.iter().enumerate() {
+            let Some(condition) = *condition else {
+                continue;
+            };
+            let fired = match condition {
+                Condition::WireEquals(index, value) => {
+                    wire(index).is_some_and(|(_, next)| next == value)
+                }
+                Condition::WireChanged(index) => {
+                    wire(index).is_some_and(|(prev, next)| prev != next)
+                }
+                Condition::CompOutput(index) => {
+                    comp(index).is_some_and(|datas| datas.into_iter().any(|d| d != 0))
+                }
+            };
+            if fired {
+                self.hits.push(BreakpointHit {
+                    id,
+                    condition,
+                    tick,
+                });
+            }
+        }
+        self.hit = self.hits.len() > first;
+        &self.hits[first..]
+    }
+}
+
+// check the conditions once the data of the tick is final, pause the circuit on a hit
+#[allow(clippy::too_many_arguments)]
+pub fn sys_check(
+    count: Res<TickCount>,
+    wires: Res<SchemaWires>,
+    comps: Res<SchemaComps>,
+    mut breakpoints: ResMut<Breakpoints>,
+    wire_query: Query<(&DataPrev, &DataNext)>,
+    comp_query: Query<&PinsOut>,
+    mut events: EventWriter<BreakpointHit>,
+    mut state: ResMut<NextState<SimState>>,
+) {
+    let wire = |index: Index| {
+        let (prev, next) = wire_query.get(*wires.0.get(index as usize)?).ok()?;
+        Some((prev.0, next.0))
+    };
+    let comp = |index: Index| {
+        let pins_out = comp_query.get(*comps.0.get(index as usize)?).ok()?;
+        let datas = pins_out.0.iter().filter_map(|id| wire_query.get(*id).ok());
+        Some(datas.map(|(_, next)| next.0))
+    };
+    let hits = breakpoints.check(count.0, wire, comp);
+    if !hits.is_empty() {
+        events.send_batch(hits.iter().copied());
+        state.set(SimState::Paused);
+    }
+}
+
+// forget the hits of the last pause, the events already told about them
+pub fn sys_resume(mut breakpoints: ResMut<Breakpoints>) {
+    breakpoints.take_hits();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+    use crate::simulator::Simulator;
+
+    fn clock_schema() -> crate::schematic::Schema {
+        let params = ClockParams {
+            high: 1,
+            low: 3,
+            phase: 0,
+        };
+        schema(
+            vec![wire(0), wire(1)],
+            vec![
+                comp(CompType::Clock(params), vec![], vec![0]),
+                comp(CompType::Fixed(0), vec![], vec![1]),
+            ],
+        )
+    }
+
+    #[test]
+    fn stops_on_conditions() {
+        on_every_backend(&clock_schema(), |sim| {
+            let equals = sim.add_breakpoint(Condition::WireEquals(0, sim.width().mask()));
+            sim.add_breakpoint(Condition::CompOutput(1));
+            assert_eq!(sim.step(10), 1);
+            assert_eq!(sim.step(10), 4);
+            let hits = sim.take_hits();
+            assert_eq!(hits.iter().map(|hit| hit.tick).collect::<Vec<_>>(), [1, 5]);
+            assert!(hits.iter().all(|hit| hit.id == equals));
+
+            // the wire changes twice per period
+            assert!(sim.remove_breakpoint(equals));
+            assert!(!sim.remove_breakpoint(equals));
+            let changed = sim.add_breakpoint(Condition::WireChanged(0));
+            assert_eq!(sim.step(10), 1);
+            assert_eq!(sim.step(10), 3);
+            let hits = sim.take_hits();
+            assert_eq!(hits.iter().map(|hit| hit.tick).collect::<Vec<_>>(), [6, 9]);
+            assert!(hits.iter().all(|hit| hit.id == changed));
+            hits
+        });
+    }
+
+    #[test]
+    fn pauses_the_scheduler() {
+        let mut sim = Simulator::new(clock_schema()).unwrap();
+        sim.add_breakpoint(Condition::WireEquals(0, sim.width().mask()));
+        let app = sim.app_mut().unwrap();
+        app.world.resource_mut::<TickScheduler>().rate = TickRate::PerFrame(3);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, 1);
+        let events = app.world.resource::<Events<BreakpointHit>>();
+        assert_eq!(events.len(), 1);
+
+        // the state changes at the start of the next frame
+        app.update();
+        let state = app.world.resource::<State<SimState>>();
+        assert_eq!(*state.get(), SimState::Paused);
+        assert_eq!(app.world.resource::<TickCount>().0, 1);
+
+        // the hit is dropped on resume, the next one is three ticks away
+        let mut next = app.world.resource_mut::<NextState<SimState>>();
+        next.set(SimState::Running);
+        app.update();
+        assert_eq!(app.world.resource::<TickCount>().0, 4);
+        assert!(app
+            .world
+            .resource_mut::<Breakpoints>()
+            .take_hits()
+            .is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::circuit::*;
+use crate::schematic::{Index, SchemaWires};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+// times the data must repeat before the wire is said to cycle
+const REPEATS: usize = 3;
+
+// wire which data repeats with a short period, kept when the cycle is found or when its
+// period changes, with the tick of the last repeat
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WireCycle {
+    pub wire: Index,
+    pub period: usize,
+    pub tick: u64,
+}
+
+/* Cycle Detector: wires which data repeats with a short period */
+// loops toggling forever show up as wires with a period of a few ticks, so do clocks
+#[derive(Resource)]
+pub struct CycleDetector {
+    wires: Vec<Index>,
+    max_period: usize,
+    // data of each watched wire on the last ticks, oldest first
+    samples: Vec<VecDeque<Data>>,
+    // period of each watched wire currently cycling
+    periods: Vec<Option<usize>>,
+    cycles: Vec<WireCycle>,
+}
+
+impl CycleDetector {
+    // watch the given wires for periods of at most `max_period` ticks, a period is at least
+    // two ticks long since a constant wire does not cycle
+    pub fn new(wires: Vec<Index>, max_period: usize) -> Self {
+        let max_period = max_period.max(2);
+        let capacity = max_period * REPEATS;
+        Self {
+            samples: vec![VecDeque::with_capacity(capacity); wires.len()],
+            periods: vec![None; wires.len()],
+            wires,
+            max_period,
+            cycles: Vec::new(),
+        }
+    }
+
+    // period of the watched wire if it currently cycles
+    pub fn period(&self, wire: Index) -> Option<usize> {
+        let i = self.wires.iter().position(|w| *w == wire)?;
+        self.periods[i]
+    }
+
+    // cycles found since the last call
+    pub fn take_cycles(&mut self) -> Vec<WireCycle> {
+        std::mem::take(&mut self.cycles)
+    }
+
+    // sample the data of every watched wire at the end of the tick,
+    // keep the cycles found on it
+    pub fn check(&mut self, tick: u64, mut read: impl FnMut(Index) -> Data) {
+        let capacity = self.max_period * REPEATS;
+        for (i, wire) in self.wires.iter().enumerate() {
+            let samples = &mut self.samples[i];
+            if samples.len() == capacity {
+                samples.pop_front();
+            }
+            samples.push_back(read(*wire));
+
+            let period = find_period(samples, self.max_period);
+            match period {
+                Some(found) if period != self.periods[i] => self.cycles.push(WireCycle {
+                    wire: *wire,
+                    period: found,
+                    tick,
+                }),
+                _ => {}
+            }
+            self.periods[i] = period;
+        }
+    }
+}
+
+// shortest period which repeated on the last samples, constant samples have none
+fn find_period(samples: &VecDeque<Data>, max_period: usize) -> Option<usize> {
+    (2..=max_period).find(|period| {
+        let len = period * REPEATS;
+        if len > samples.len() {
+            return false;
+        }
+        let start = samples.len() - len;
+        let constant = samples.range(start..).all(|data| *data == samples[start]);
+        !constant && (start + period..samples.len()).all(|i| samples[i] == samples[i - period])
+    })
+}
+
+// sample the data of the watched wires once the data of the tick is final
+pub fn sys_detect(
+    count: Res<TickCount>,
+    wires: Res<SchemaWires>,
+    mut detector: ResMut<CycleDetector>,
+    query: Query<&DataNext>,
+) {
+    detector.check(count.0, |index| {
+        wires
+            .0
+            .get(index as usize)
+            .and_then(|id| query.get(*id).ok())
+            .map_or(0, |data| data.0)
+    });
+}
+
+// longest period the wires of the app are watched for once the circuit is built
+#[derive(Resource)]
+pub struct CycleWatch(pub usize);
+
+// watch every wire once the circuit is built
+pub fn start_cycles(
+    mut commands: Commands,
+    watch: Res<CycleWatch>,
+    wires: Option<Res<SchemaWires>>,
+) {
+    let Some(wires) = wires else {
+        return;
+    };
+    let wires = (0..wires.0.len() as Index).collect();
+    commands.insert_resource(CycleDetector::new(wires, watch.0));
+}
+
+// tell which wires started cycling or changed their period
+pub fn report_cycles(detector: Option<ResMut<CycleDetector>>) {
+    let Some(mut detector) = detector else {
+        return;
+    };
+    for cycle in detector.take_cycles() {
+        warn!(
+            "Wire {} cycles every {} ticks at tick {}",
+            cycle.wire, cycle.period, cycle.tick
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+
+    #[test]
+    fn finds_periods() {
+        let samples = |datas: &[Data]| datas.iter().copied().collect::<VecDeque<_>>();
+        assert_eq!(find_period(&samples(&[1, 0, 1, 0, 1, 0]), 4), Some(2));
+        assert_eq!(find_period(&samples(&[1, 0, 1, 0, 1]), 4), None);
+        assert_eq!(find_period(&samples(&[3; 12]), 4), None);
+        let three = [1, 2, 2, 1, 2, 2, 1, 2, 2];
+        assert_eq!(find_period(&samples(&three), 4), Some(3));
+        assert_eq!(find_period(&samples(&three), 2), None);
+    }
+
+    #[test]
+    fn flags_toggling_wires() {
+        let schema = schema(
+            vec![wire(0), wire(0), wire(0)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                // a Nor reading its own output toggles on every tick
+                gate(Operator::Nor, vec![0, 1], vec![1]),
+                comp(CompType::Fixed(3), vec![], vec![2]),
+            ],
+        );
+        on_every_backend(&schema, |sim| {
+            sim.start_cycle_detector(None, 4);
+            sim.step(10);
+            let cycles = sim.take_cycles();
+            let period = WireCycle {
+                wire: 1,
+                period: 2,
+                tick: 6,
+            };
+            assert_eq!(cycles, [period]);
+            assert_eq!(sim.cycle_detector().unwrap().period(1), Some(2));
+
+            // the wire settles once the other input holds the gate
+            sim.write_input(0, sim.width().mask());
+            sim.step(3);
+            assert_eq!(sim.read_wire(1), Some(0));
+            assert_eq!(sim.cycle_detector().unwrap().period(1), None);
+            assert!(sim.take_cycles().is_empty());
+            assert!(sim.stop_cycle_detector().is_some());
+            cycles
+        });
+    }
+
+    #[test]
+    fn watches_the_app() {
+        use crate::simulator::Simulator;
+        use bevy::ecs::system::RunSystemOnce;
+
+        let schema = schema(
+            vec![wire(0), wire(0)],
+            vec![
+                comp(CompType::Input, vec![], vec![0]),
+                gate(Operator::Nor, vec![0, 1], vec![1]),
+            ],
+        );
+        let mut sim = Simulator::new(schema).unwrap();
+        let app = sim.app_mut().unwrap();
+        app.insert_resource(CycleWatch(4));
+        app.world.run_system_once(start_cycles);
+        sim.step(10);
+        let cycles = sim.take_cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!((cycles[0].wire, cycles[0].period), (1, 2));
+    }
+}
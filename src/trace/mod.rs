@@ -0,0 +1,69 @@
+/**
+ * Watch the values of wires over the ticks: record and export them, stop on conditions
+ * or find the ones cycling
+ */
+mod breakpoint;
+mod cycle;
+mod recorder;
+mod vcd;
+
+pub use breakpoint::{sys_check, sys_resume, BreakpointHit, Breakpoints, Condition};
+pub use cycle::{report_cycles, start_cycles, sys_detect, CycleDetector, CycleWatch, WireCycle};
+pub use recorder::{dump_vcd, start_vcd, sys_record, TraceRecorder, VcdDump};
+pub use vcd::write_vcd;
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::*;
+    use crate::schematic::testing::*;
+    use crate::schematic::CompType;
+
+    #[test]
+    fn records_clock() {
+        let params = ClockParams {
+            high: 1,
+            low: 2,
+            phase: 0,
+        };
+        let schema = schema(
+            vec![wire(0), wire(3)],
+            vec![
+                comp(CompType::Clock(params), vec![], vec![0]),
+                comp(CompType::Fixed(5), vec![], vec![1]),
+            ],
+        );
+        on_every_backend(&schema, |sim| {
+            sim.step(2);
+            assert!(sim.trace().is_none());
+            sim.start_trace(None, 3);
+            sim.step(4);
+            let trace = sim.trace().unwrap();
+            let samples: Vec<(u64, Vec<Data>)> = trace
+                .samples()
+                .map(|(tick, values)| (tick, values.to_vec()))
+                .collect();
+            let on = sim.width().mask();
+            assert_eq!(
+                samples,
+                [(4, vec![0, 5]), (5, vec![on, 5]), (6, vec![0, 5])]
+            );
+
+            let path = temp_path("clock.vcd");
+            trace.save_vcd(&path).unwrap();
+            let vcd = std::fs::read_to_string(&path).unwrap();
+            assert!(vcd.contains("$var wire 16 ! wire0_ch0 $end\n"));
+            assert!(vcd.contains("$var wire 16 \" wire1_ch3 $end\n"));
+            let changes = "#4\nb0 !\nb101 \"\n#5\nb1111111111111111 !\n#6\nb0 !\n";
+            assert!(vcd.ends_with(changes), "{}", vcd);
+
+            // the recorder is handed back with its samples
+            let mut trace = sim.stop_trace().unwrap();
+            sim.step(1);
+            assert!(sim.trace().is_none());
+            assert_eq!(trace.samples().count(), 3);
+            trace.clear();
+            assert_eq!(trace.samples().count(), 0);
+            vcd
+        });
+    }
+}
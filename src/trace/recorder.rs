@@ -0,0 +1,123 @@
+use crate::circuit::*;
+use crate::schematic::{Index, SchemaWires};
+use bevy::{app::AppExit, prelude::*};
+use std::collections::VecDeque;
+use std::{fs, io, path};
+
+/* Trace Recorder: values of the selected wires on the last ticks */
+#[derive(Resource)]
+pub struct TraceRecorder {
+    // index in the schematic and channel of each recorded wire
+    wires: Vec<(Index, Channel)>,
+    width: DataWidth,
+    // oldest samples are dropped once the capacity is reached
+    capacity: usize,
+    samples: VecDeque<(u64, Vec<Data>)>,
+}
+
+impl TraceRecorder {
+    // record the given wires of the given width, keep at most `capacity` ticks
+    pub fn new(wires: Vec<(Index, Channel)>, width: DataWidth, capacity: usize) -> Self {
+        Self {
+            wires,
+            width,
+            capacity,
+            samples: VecDeque::with_capacity(capacity.min(1 << 16)),
+        }
+    }
+
+    // index in the schematic and channel of each recorded wire
+    pub fn wires(&self) -> &[(Index, Channel)] {
+        &self.wires
+    }
+
+    // number of bits of the recorded wires
+    pub fn width(&self) -> DataWidth {
+        self.width
+    }
+
+    // sample the value of every recorded wire on the given tick
+    pub fn record(&mut self, tick: u64, mut read: impl FnMut(Index) -> Data) {
+        if self.capacity == 0 {
+            return;
+        }
+        let values = self.wires.iter().map(|(index, _)| read(*index)).collect();
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((tick, values));
+    }
+
+    // recorded ticks from the oldest one, with the values ordered like the wires
+    pub fn samples(&self) -> impl Iterator<Item = (u64, &[Data])> {
+        self.samples
+            .iter()
+            .map(|(tick, values)| (*tick, values.as_slice()))
+    }
+
+    // forget the recorded ticks
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    // write the recorded ticks as a Value Change Dump
+    pub fn save_vcd<P: AsRef<path::Path>>(&self, path: P) -> io::Result<()> {
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        super::write_vcd(self, file)
+    }
+}
+
+// file the recorded ticks are written to when the app exits, every wire is recorded
+// and only the last `capacity` ticks are kept
+#[derive(Resource)]
+pub struct VcdDump {
+    pub path: path::PathBuf,
+    pub capacity: usize,
+}
+
+// record every wire once the circuit is built
+pub fn start_vcd(
+    mut commands: Commands,
+    dump: Res<VcdDump>,
+    width: Res<DataWidth>,
+    wires: Option<Res<SchemaWires>>,
+    query: Query<&PinChannel>,
+) {
+    let Some(wires) = wires else {
+        return;
+    };
+    let wires = (wires.0.iter().enumerate())
+        .filter_map(|(i, id)| Some((i as Index, query.get(*id).ok()?.0)))
+        .collect();
+    commands.insert_resource(TraceRecorder::new(wires, *width, dump.capacity));
+}
+
+// write the recorded ticks when the app exits
+pub fn dump_vcd(
+    mut exit: EventReader<AppExit>,
+    dump: Res<VcdDump>,
+    recorder: Option<Res<TraceRecorder>>,
+) {
+    let (Some(_), Some(recorder)) = (exit.read().next(), recorder) else {
+        return;
+    };
+    if let Err(e) = recorder.save_vcd(&dump.path) {
+        error!("Cannot write {}: {}", dump.path.display(), e);
+    }
+}
+
+// sample the data of the recorded wires during the tick
+pub fn sys_record(
+    count: Res<TickCount>,
+    wires: Res<SchemaWires>,
+    mut recorder: ResMut<TraceRecorder>,
+    query: Query<&DataPrev>,
+) {
+    recorder.record(count.0, |index| {
+        wires
+            .0
+            .get(index as usize)
+            .and_then(|id| query.get(*id).ok())
+            .map_or(0, |pin| pin.0)
+    });
+}
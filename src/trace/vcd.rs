@@ -0,0 +1,67 @@
+use super::TraceRecorder;
+use crate::circuit::Data;
+use std::io;
+
+// identifier of a variable, printable characters from '!' to '~' used as digits
+fn identifier(mut n: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return id;
+        }
+        n -= 1;
+    }
+}
+
+// write the recorded ticks as a Value Change Dump, one tick lasts one nanosecond,
+// wires are named after their index in the schematic and their channel
+pub fn write_vcd<W: io::Write>(recorder: &TraceRecorder, mut out: W) -> io::Result<()> {
+    writeln!(out, "$version bevy-logic-circuit $end")?;
+    writeln!(out, "$timescale 1ns $end")?;
+    writeln!(out, "$scope module circuit $end")?;
+    for (n, (index, channel)) in recorder.wires().iter().enumerate() {
+        let bits = recorder.width().0;
+        writeln!(
+            out,
+            "$var wire {} {} wire{}_ch{} $end",
+            bits,
+            identifier(n),
+            index,
+            channel
+        )?;
+    }
+    writeln!(out, "$upscope $end")?;
+    writeln!(out, "$enddefinitions $end")?;
+
+    // the first tick dumps every wire, the next ones only the wires which changed
+    let mut last: Option<&[Data]> = None;
+    for (tick, values) in recorder.samples() {
+        let changed: Vec<usize> = (0..values.len())
+            .filter(|i| last.is_none_or(|last| last[*i] != values[*i]))
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+        writeln!(out, "#{}", tick)?;
+        for i in changed {
+            writeln!(out, "b{:b} {}", values[i], identifier(i))?;
+        }
+        last = Some(values);
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier(0), "!");
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+        assert_eq!(identifier(95), "\"!");
+    }
+}